@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+
+use jwt_libs::types::JwtConfig;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Default, PartialEq, Eq)]
@@ -30,11 +33,82 @@ pub struct Logger{
     log: String
 }
 
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct RefreshToken{
+    pub purge_interval: u64
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct Mail{
+    pub sender: String,
+    pub from: String,
+    pub file_path: Option<String>,
+    pub verify_url: String,
+    pub verification_token_lifetime: i64,
+    pub reset_url: String,
+    pub reset_token_lifetime: i64
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct LoginLimit{
+    pub window: i64,
+    pub account_attempts: u32,
+    pub ip_attempts: u32,
+    pub max_failures: i64,
+    pub failure_window: i64,
+    pub lockout: i64
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct Mfa{
+    pub issuer: String,
+    pub recovery_codes: usize
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct OAuthProviderConfig{
+    pub client_id: String,
+    pub client_secret: String,
+    pub authorization_url: String,
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub subject_field: String,
+    pub email_field: String,
+    pub email_verified_field: Option<String>,
+    pub username_field: Option<String>
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct OAuth{
+    pub state_lifetime: i64,
+    #[serde(default)]
+    pub providers: HashMap<String, OAuthProviderConfig>
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct Oidc{
+    pub base_url: String,
+    pub code_lifetime: i64,
+    pub login_url: String,
+    pub consent_url: String,
+    pub session_lifetime: i64,
+    pub consent_lifetime: i64
+}
+
 #[derive(Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct UserAppConfig{
  pub apps: Apps,
  pub database: Database,
  pub redis: Redis,
  pub rabbitmq: RabbitMq,
- pub logger: Logger
+ pub logger: Logger,
+ pub jwt: JwtConfig,
+ pub refresh_token: RefreshToken,
+ pub mail: Mail,
+ pub login_limit: LoginLimit,
+ pub mfa: Mfa,
+ pub oauth: OAuth,
+ pub oidc: Oidc
 }
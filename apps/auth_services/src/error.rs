@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt};
+
+use actix_web::{http::{header::{CACHE_CONTROL, RETRY_AFTER, WWW_AUTHENTICATE}, StatusCode}, HttpResponse, ResponseError};
+use logger_libs::Logger;
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    Validation { message: String, fields: HashMap<String, Vec<String>> },
+    Conflict(String),
+    InvalidCredentials,
+    NotFound(String),
+    Unauthorized(String),
+    Forbidden(String),
+    RateLimited { message: String, retry_after: i64 },
+    Upstream(String),
+    Database(String),
+    Internal(String),
+}
+
+impl AuthError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        AuthError::Validation { message: message.into(), fields: HashMap::new() }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            AuthError::Validation { .. } => "Validation",
+            AuthError::Conflict(_) => "Conflict",
+            AuthError::InvalidCredentials => "InvalidCredentials",
+            AuthError::NotFound(_) => "NotFound",
+            AuthError::Unauthorized(_) => "Unauthorized",
+            AuthError::Forbidden(_) => "Forbidden",
+            AuthError::RateLimited { .. } => "RateLimited",
+            AuthError::Upstream(_) => "Upstream",
+            AuthError::Database(_) => "Database",
+            AuthError::Internal(_) => "Internal",
+        }
+    }
+
+    // Database and internal details stay in the logs; clients only learn that the request failed on our side.
+    fn public_message(&self) -> String {
+        match self {
+            AuthError::Database(_) | AuthError::Internal(_) => String::from("internal server error"),
+            AuthError::Upstream(_) => String::from("upstream service unavailable"),
+            _ => self.to_string(),
+        }
+    }
+}
+
+// Handlers log a failed step and pass the error on, so it still reaches the client through ResponseError.
+pub fn log_failure(handler_name: &str, log_id: &str, title: &str, error: AuthError) -> AuthError {
+    Logger::warning_logger(handler_name, log_id, title, &error.to_string());
+    error
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::Validation { message, .. } => write!(f, "{}", message),
+            AuthError::Conflict(message) => write!(f, "{}", message),
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+            AuthError::NotFound(message) => write!(f, "{}", message),
+            AuthError::Unauthorized(message) => write!(f, "{}", message),
+            AuthError::Forbidden(message) => write!(f, "{}", message),
+            AuthError::RateLimited { message, .. } => write!(f, "{}", message),
+            AuthError::Upstream(message) => write!(f, "upstream error: {}", message),
+            AuthError::Database(message) => write!(f, "database error: {}", message),
+            AuthError::Internal(message) => write!(f, "internal error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<sqlx::Error> for AuthError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => AuthError::NotFound(String::from("record not found")),
+            sqlx::Error::Database(database_error) if database_error.is_unique_violation() => {
+                AuthError::Conflict(String::from("record already exists"))
+            },
+            _ => AuthError::Database(error.to_string()),
+        }
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::Validation { .. } => StatusCode::BAD_REQUEST,
+            AuthError::Conflict(_) => StatusCode::CONFLICT,
+            AuthError::InvalidCredentials | AuthError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AuthError::NotFound(_) => StatusCode::NOT_FOUND,
+            AuthError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AuthError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AuthError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            AuthError::Database(_) | AuthError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let AuthError::RateLimited { retry_after, .. } = self {
+            response.insert_header((RETRY_AFTER, retry_after.to_string()));
+        }
+
+        let mut body = json!({
+            "status": "failed",
+            "error": self.kind(),
+            "message": self.public_message()
+        });
+        if let AuthError::Validation { fields, .. } = self {
+            if !fields.is_empty() {
+                body["fields"] = json!(fields);
+            }
+        }
+
+        response.json(body)
+    }
+}
+
+// The OAuth token endpoint answers with RFC 6749 error codes instead of the service envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthError {
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl OAuthError {
+    pub fn new(error: &'static str, description: impl Into<String>) -> Self {
+        OAuthError { error, description: description.into() }
+    }
+}
+
+impl fmt::Display for OAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.error, self.description)
+    }
+}
+
+impl From<AuthError> for OAuthError {
+    fn from(error: AuthError) -> Self {
+        let code = match &error {
+            AuthError::Validation { .. } | AuthError::Conflict(_) | AuthError::RateLimited { .. } => "invalid_request",
+            AuthError::InvalidCredentials | AuthError::NotFound(_) | AuthError::Unauthorized(_) | AuthError::Forbidden(_) => "invalid_grant",
+            AuthError::Upstream(_) | AuthError::Database(_) | AuthError::Internal(_) => "server_error",
+        };
+        OAuthError::new(code, error.public_message())
+    }
+}
+
+impl ResponseError for OAuthError {
+    fn status_code(&self) -> StatusCode {
+        match self.error {
+            "invalid_client" => StatusCode::UNAUTHORIZED,
+            "server_error" => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        response.insert_header((CACHE_CONTROL, "no-store"));
+        if self.error == "invalid_client" {
+            response.insert_header((WWW_AUTHENTICATE, "Basic"));
+        }
+
+        response.json(json!({
+            "error": self.error,
+            "error_description": self.description
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::body::to_bytes;
+
+    use super::*;
+
+    async fn body(error: AuthError) -> serde_json::Value {
+        let bytes = to_bytes(error.error_response().into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[actix_web::test]
+    async fn maps_variants_to_status_codes() {
+        assert_eq!(AuthError::validation("bad").status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(AuthError::InvalidCredentials.status_code(), StatusCode::UNAUTHORIZED);
+        assert_eq!(AuthError::NotFound(String::from("user not found")).status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(AuthError::Upstream(String::from("rabbitmq")).status_code(), StatusCode::BAD_GATEWAY);
+        assert_eq!(AuthError::from(sqlx::Error::RowNotFound).status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn hides_database_details() {
+        let body = body(AuthError::Database(String::from("relation \"user\" does not exist"))).await;
+
+        assert_eq!(body["error"], "Database");
+        assert_eq!(body["message"], "internal server error");
+    }
+
+    #[actix_web::test]
+    async fn rate_limited_sets_retry_after() {
+        let response = AuthError::RateLimited { message: String::from("slow down"), retry_after: 30 }.error_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers().get(RETRY_AFTER).unwrap(), "30");
+    }
+
+    #[actix_web::test]
+    async fn oauth_error_uses_rfc_6749_body() {
+        let response = OAuthError::new("invalid_client", "unknown client").error_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(response.headers().get(WWW_AUTHENTICATE).unwrap(), "Basic");
+
+        let error = OAuthError::from(AuthError::Database(String::from("connection reset")));
+        assert_eq!(error.error, "server_error");
+        assert_eq!(error.description, "internal server error");
+        assert_eq!(OAuthError::from(AuthError::Unauthorized(String::from("expired"))).error, "invalid_grant");
+    }
+}
@@ -1,25 +1,36 @@
 use actix_web::{
     get, middleware::Logger, web::{self, scope}, App, HttpResponse, HttpServer, Responder
 };
-use config_type::UserAppConfig;
+use config_type::{LoginLimit, Mail, Mfa, OAuth, Oidc, UserAppConfig};
+use jwt_libs::keys::JwtKeyring;
 use lapin::{options::{BasicPublishOptions, QueueDeclareOptions}, types::FieldTable, BasicProperties};
 
-use modules::user::handler::{auth_config, token_config, user_config};
+use modules::{admin::handler::admin_config, mail::{consumer::spawn_mail_consumers, sender::mail_sender}, mfa::totp::SecretCipher, oauth::provider::IdentityProviders, oidc::{handler::{discovery_config, oidc_config}, service::validate_issuer}};
+use modules::user::{handler::{auth_config, jwks_config, token_config, user_config}, service::UserServices};
 use pgsql_libs::{create_db_pool, DbPool};
 use r2d2_redis::redis::{Commands, RedisError};
 use serde_json::json;
+use std::time::Duration;
 use dotenv::{dotenv, var};
-use env_logger;
 use redis_libs::{RedisPool,redis_connect};
 mod middlewares;
 mod modules;
 use logger_libs::Logger as service_logger;
 mod config_type;
+mod error;
 use rabbitmq_libs::{RabbitMqPool,rabbit_connect};
 pub struct AppState {
     db: DbPool,
     redis: RedisPool ,
-    rabbit: RabbitMqPool
+    rabbit: RabbitMqPool,
+    jwt: JwtKeyring,
+    mail: Mail,
+    login_limit: LoginLimit,
+    mfa: Mfa,
+    secret_cipher: SecretCipher,
+    oauth: OAuth,
+    identity_providers: IdentityProviders,
+    oidc: Oidc
 }
 
 #[actix_web::main]
@@ -46,6 +57,34 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    let key_dir = match var("JWT_KEY_DIR") {
+        Ok(dir)=>dir,
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.jwt_key_dir", &error);
+            panic!("JWT_KEY_DIR: {}",error)
+        }
+    };
+
+    let jwt_keyring: JwtKeyring = match JwtKeyring::from_config(&config.jwt.clone().with_key_dir(&key_dir)){
+        Ok(keyring)=>{
+            if let Err(error) = keyring.signing_key(){
+                service_logger::err_logger(handler_name,"main", "main.jwt_keyring", &error);
+                panic!("{}",error)
+            }
+            service_logger::info_logger(handler_name,"main", "main.jwt_keyring");
+            keyring
+        },
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.jwt_keyring", &error);
+            panic!("{}",error)
+        }
+    };
+
+    if let Err(error) = validate_issuer(&config.oidc.base_url, &jwt_keyring.claims().issuer){
+        service_logger::err_logger(handler_name,"main", "main.jwt_issuer", &error);
+        panic!("{}",error)
+    }
+
     let db_url: String= config.database.url;
 
     let db_pool: DbPool= match create_db_pool(db_url, 5, 50).await {
@@ -79,27 +118,83 @@ async fn main() -> std::io::Result<()> {
             rb_pool
         },
         Err(error)=>{
-            service_logger::err_logger(handler_name,"main", "main.rabbitmq_connections", &error);
+            service_logger::err_logger(handler_name,"main", "main.rabbitmq_connections", error);
+            panic!("{}",error)
+        }
+    };
+
+    let mail_sender = match mail_sender(&config.mail){
+        Ok(sender)=>{
+            service_logger::info_logger(handler_name,"main", "main.mail_sender");
+            sender
+        },
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.mail_sender", &error);
+            panic!("{}",error)
+        }
+    };
+
+    let encryption_key = match var("MFA_ENCRYPTION_KEY") {
+        Ok(key)=>key,
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.mfa_encryption_key", &error);
+            panic!("MFA_ENCRYPTION_KEY: {}",error)
+        }
+    };
+
+    let secret_cipher = match SecretCipher::from_hex(&encryption_key){
+        Ok(cipher)=>{
+            service_logger::info_logger(handler_name,"main", "main.secret_cipher");
+            cipher
+        },
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.secret_cipher", &error);
             panic!("{}",error)
         }
     };
 
+    let identity_providers = match IdentityProviders::from_config(&config.oauth.providers){
+        Ok(providers)=>{
+            service_logger::info_logger(handler_name,"main", "main.identity_providers");
+            providers
+        },
+        Err(error)=>{
+            service_logger::err_logger(handler_name,"main", "main.identity_providers", &error);
+            panic!("{}",error)
+        }
+    };
+
+    spawn_mail_consumers(rabbit_pool.clone(), config.mail.clone(), mail_sender);
+    UserServices::spawn_refresh_token_purge(db_pool.clone(), Duration::from_secs(config.refresh_token.purge_interval));
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(AppState
                 {
                     db: db_pool.clone(), 
                     redis: redis_pool.clone(), 
-                    rabbit:rabbit_pool.clone()
+                    rabbit:rabbit_pool.clone(),
+                    jwt: jwt_keyring.clone(),
+                    mail: config.mail.clone(),
+                    login_limit: config.login_limit.clone(),
+                    mfa: config.mfa.clone(),
+                    secret_cipher: secret_cipher.clone(),
+                    oauth: config.oauth.clone(),
+                    identity_providers: identity_providers.clone(),
+                    oidc: config.oidc.clone()
                 }
             ))
             .wrap(Logger::default())
+            .configure(jwks_config)
+            .configure(discovery_config)
             .service(
                 scope("/api")
                     .service(api_health_check)
                     .configure(auth_config)
                     .configure(token_config)
                     .configure(user_config)
+                    .configure(admin_config)
+                    .configure(oidc_config)
             )
     })
     .bind(("0.0.0.0", 8080))?
@@ -120,7 +215,7 @@ async fn api_health_check(data: web::Data<AppState>) -> impl Responder {
         }));
     }
 
-    service_logger::info_logger(&handler_name,log_id, "healtcheck.database_check");
+    service_logger::info_logger(handler_name,log_id, "healtcheck.database_check");
 
     match data.redis.get() {
         Ok(mut conn) => {
@@ -131,7 +226,6 @@ async fn api_health_check(data: web::Data<AppState>) -> impl Responder {
                     error_messages.push(json!({
                         "database": format!("❌ Cannot connect to redis: {}", error)
                     }));
-                    ()
                 }
             };
             let redis_value: Result<String,RedisError> = match conn.get("testing_redis") {
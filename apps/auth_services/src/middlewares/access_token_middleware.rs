@@ -1,16 +1,31 @@
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, 
+    error::ErrorInternalServerError,
+    http::header::{HeaderMap, AUTHORIZATION},
     web::Data,
     Error, 
     HttpMessage, 
 };
 use futures::future::{ok, LocalBoxFuture, Ready};
-use r2d2_redis::redis::Commands;
 
-use  crate::AppState;
-use jwt_libs::decode_access_token;
+use  crate::{error::AuthError, AppState};
+use jwt_libs::{decode_access_token, error::JwtLibError, keys::JwtKeyring, types::{AccessToken, TokenClaims}};
+use redis_libs::{is_subject_disabled, is_token_revoked};
 use super::refresh_token_middleware::UnauthorizedError;
 
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+pub fn verify_access_token(token: &str, keyring: &JwtKeyring) -> Result<TokenClaims<AccessToken>, JwtLibError> {
+    decode_access_token(token, keyring).map(|token_data| token_data.claims)
+}
+
 pub struct AccessTokenMW;
 
 impl<S, B> Transform<S, ServiceRequest> for AccessTokenMW
@@ -50,30 +65,42 @@ where
         let app_state: Option<&Data<AppState>> = req.app_data::<Data<AppState>>();
 
         if let Some(state) = app_state {
-            let mut redis_conn = match state.redis.get() {
-                Ok(conn) => conn,
-                Err(_error) => {
-                    return Box::pin(async { Err(UnauthorizedError.into()) })
-                },
-            };
-
-            let redis_key = "access_token".to_string();
-
-            let refresh_token = match redis_conn.get::<String, String>(redis_key) {
-                Ok(token) => token,
-                Err(_) => {
+            let access_token = match bearer_token(req.headers()) {
+                Some(token) => token,
+                None => {
                     return Box::pin(async { Err(UnauthorizedError.into()) });
                 }
             };
 
-            let user = match decode_access_token(&refresh_token) {
-                Ok(user_token) => user_token.claims.token,
-                Err(_) => {
-                    return Box::pin(async { Err(UnauthorizedError.into()) });
+            let claims = match verify_access_token(&access_token, &state.jwt) {
+                Ok(claims) => claims,
+                Err(error) => {
+                    return Box::pin(async { Err(error.into()) });
                 }
             };
 
-            req.extensions_mut().insert(user);
+            match is_token_revoked(&state.redis, &claims.jti, &claims.sub, claims.iat) {
+                Ok(false) => {},
+                Ok(true) => {
+                    return Box::pin(async { Err(JwtLibError::Revoked.into()) });
+                }
+                Err(error) => {
+                    return Box::pin(async { Err(ErrorInternalServerError(error)) });
+                }
+            }
+
+            match is_subject_disabled(&state.redis, &claims.sub) {
+                Ok(false) => {},
+                Ok(true) => {
+                    return Box::pin(async { Err(AuthError::Forbidden(String::from("account disabled")).into()) });
+                }
+                Err(error) => {
+                    return Box::pin(async { Err(ErrorInternalServerError(error)) });
+                }
+            }
+
+            req.extensions_mut().insert(claims.token.clone());
+            req.extensions_mut().insert(claims);
             
             let fut = self.service.call(req);
 
@@ -85,4 +112,127 @@ where
 
         Box::pin(async { Err(UnauthorizedError.into()) })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test, web, App, HttpRequest, HttpResponse};
+    use jwt_libs::{
+        generate_access_token, generate_client_access_token, generate_refresh_token,
+        testing::{access_token, keyring},
+        types::{ClientAccessToken, RefreshToken},
+    };
+    use redis_libs::{revoke_token, set_subject_disabled, testing::test_redis_pool, RedisPool};
+    use sqlx::postgres::PgPoolOptions;
+    use uuid::Uuid;
+
+    use crate::modules::mfa::totp::SecretCipher;
+
+    use super::*;
+
+    fn app_state(jwt: JwtKeyring, redis: RedisPool) -> Data<AppState> {
+        Data::new(AppState {
+            db: PgPoolOptions::new().connect_lazy("postgres://localhost/test").unwrap(),
+            redis,
+            rabbit: rabbitmq_libs::rabbit_connect(String::from("amqp://localhost:5672"), 1).unwrap(),
+            jwt,
+            mail: Default::default(),
+            login_limit: Default::default(),
+            mfa: Default::default(),
+            secret_cipher: SecretCipher::from_hex(&"00".repeat(32)).unwrap(),
+            oauth: Default::default(),
+            identity_providers: Default::default(),
+            oidc: Default::default(),
+        })
+    }
+
+    async fn whoami(req: HttpRequest) -> HttpResponse {
+        match req.extensions().get::<AccessToken>() {
+            Some(user) => HttpResponse::Ok().body(user.id.to_string()),
+            None => HttpResponse::InternalServerError().finish(),
+        }
+    }
+
+    async fn call_with_header(authorization: Option<String>) -> Result<ServiceResponse, Error> {
+        call_with_redis(authorization, test_redis_pool()).await
+    }
+
+    async fn call_with_redis(authorization: Option<String>, redis: RedisPool) -> Result<ServiceResponse, Error> {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_state(keyring(), redis))
+                .service(web::scope("/user").wrap(AccessTokenMW).route("/me", web::get().to(whoami))),
+        )
+        .await;
+
+        let mut req = test::TestRequest::get().uri("/user/me");
+        if let Some(value) = authorization {
+            req = req.insert_header((AUTHORIZATION, value));
+        }
+
+        test::try_call_service(&app, req.to_request()).await
+    }
+
+    #[actix_web::test]
+    async fn accepts_bearer_access_token() {
+        let user = access_token().build();
+        let token = generate_access_token(user.clone(), &keyring()).unwrap();
+
+        let res = call_with_header(Some(format!("Bearer {}", token))).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(test::read_body(res).await, user.id.to_string());
+    }
+
+    #[actix_web::test]
+    async fn rejects_missing_header() {
+        let error = call_with_header(None).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn rejects_non_bearer_header() {
+        let error = call_with_header(Some(String::from("Basic dXNlcjpwYXNz"))).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn rejects_refresh_token() {
+        let token = generate_refresh_token(RefreshToken { id: Uuid::new_v4() }, &keyring()).unwrap();
+
+        let error = call_with_header(Some(format!("Bearer {}", token))).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn rejects_client_access_token() {
+        let client = ClientAccessToken { id: Uuid::new_v4(), client_id: String::from("auth_services"), scopes: vec![String::from("openid")] };
+        let token = generate_client_access_token(client, &keyring()).unwrap();
+
+        let error = call_with_header(Some(format!("Bearer {}", token))).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn rejects_revoked_token() {
+        let token = generate_access_token(access_token().build(), &keyring()).unwrap();
+        let claims = verify_access_token(&token, &keyring()).unwrap();
+
+        let redis = test_redis_pool();
+        revoke_token(&redis, &claims.jti, claims.remaining_lifetime()).unwrap();
+
+        let error = call_with_redis(Some(format!("Bearer {}", token)), redis).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn rejects_disabled_user() {
+        let user = access_token().build();
+        let token = generate_access_token(user.clone(), &keyring()).unwrap();
+
+        let redis = test_redis_pool();
+        set_subject_disabled(&redis, &user.id.to_string(), true).unwrap();
+
+        let error = call_with_redis(Some(format!("Bearer {}", token)), redis).await.unwrap_err();
+        assert_eq!(error.error_response().status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,71 @@
+use std::ops::Deref;
+
+use actix_web::{dev::Payload, FromRequest, HttpMessage, HttpRequest};
+use futures::future::{ready, Ready};
+use jwt_libs::types::AccessToken;
+
+use crate::error::AuthError;
+
+pub const ADMIN_ROLE: &str = "admin";
+
+// Reads the access token that `AccessTokenMW` attached to the request, so it only works behind that middleware.
+pub struct AuthUser(pub AccessToken);
+
+impl AuthUser {
+    pub fn require_role(&self, role: &str) -> Result<&AccessToken, AuthError> {
+        match self.0.has_role(role) {
+            true => Ok(&self.0),
+            false => Err(AuthError::Forbidden(format!("missing role: {}", role))),
+        }
+    }
+}
+
+impl Deref for AuthUser {
+    type Target = AccessToken;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = AuthError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<AccessToken>()
+                .cloned()
+                .map(AuthUser)
+                .ok_or(AuthError::Unauthorized(String::from("token not found"))),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test::TestRequest, ResponseError};
+    use jwt_libs::testing::access_token;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn extracts_token_from_extensions() {
+        let req = TestRequest::default().to_http_request();
+        let error = AuthUser::extract(&req).await.err().unwrap();
+        assert_eq!(error.status_code(), StatusCode::UNAUTHORIZED);
+
+        req.extensions_mut().insert(access_token().build());
+        assert_eq!(AuthUser::extract(&req).await.unwrap().username, "tester");
+    }
+
+    #[test]
+    fn requires_role() {
+        let admin = AuthUser(access_token().role("admin").build());
+        let member = AuthUser(access_token().build());
+
+        assert!(admin.require_role("admin").is_ok());
+        assert_eq!(member.require_role("admin").unwrap_err().status_code(), StatusCode::FORBIDDEN);
+    }
+}
@@ -1,2 +1,3 @@
 pub mod refresh_token_middleware;
-pub mod access_token_middleware;
\ No newline at end of file
+pub mod access_token_middleware;
+pub mod authorization;
\ No newline at end of file
@@ -1,11 +1,15 @@
 use std::fmt;
 
 use actix_web::{
-    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, http::header::HeaderValue, Error, HttpMessage, HttpResponse, ResponseError
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform}, error::ErrorInternalServerError, http::header::HeaderValue, web::Data, Error, HttpMessage, HttpResponse, ResponseError
 };
 use futures::future::{ok, LocalBoxFuture, Ready};
+use jwt_libs::decode_refresh_token;
+use redis_libs::is_subject_disabled;
 use serde_json::json;
 
+use crate::{error::AuthError, AppState};
+
 #[derive(Debug)]
 pub struct UnauthorizedError;
 
@@ -62,8 +66,19 @@ where
         
         if let Some(token) = refresh_token {
           
-            if let Ok(token_str) = token.to_str() {
-                
+            if let (Ok(token_str), Some(state)) = (token.to_str(), req.app_data::<Data<AppState>>()) {
+
+                let claims = match decode_refresh_token(token_str, &state.jwt) {
+                    Ok(token_data) => token_data.claims,
+                    Err(error) => return Box::pin(async { Err(error.into()) }),
+                };
+
+                match is_subject_disabled(&state.redis, &claims.sub) {
+                    Ok(false) => {},
+                    Ok(true) => return Box::pin(async { Err(AuthError::Forbidden(String::from("account disabled")).into()) }),
+                    Err(error) => return Box::pin(async { Err(ErrorInternalServerError(error)) }),
+                }
+
                 req.extensions_mut().insert(token_str.to_string());
 
                 let fut = self.service.call(req);
@@ -0,0 +1,174 @@
+use actix_web::{get, post, put, web::{scope, Data, Json, Path, Query, ServiceConfig}, HttpResponse};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{error::{log_failure, AuthError}, middlewares::{access_token_middleware::AccessTokenMW, authorization::{AuthUser, ADMIN_ROLE}}, modules::{oidc::handler::{create_client_handler, delete_client_handler, list_clients_handler}, user::handler::json_validate}, AppState};
+
+use super::{model::{AdminActionData, AssignRolesData, UserListQuery}, service::AdminServices};
+
+#[get("/users")]
+async fn list_users_handler(
+    user: AuthUser,
+    query: Query<UserListQuery>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_list_users_handler";
+    let log_id = format!("{} Admin.Users",chrono::Utc::now());
+    user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_list_users.require_role", error))?;
+
+    let users = AdminServices::list_users(&log_id, query.into_inner(), &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "admin_list_users.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get users success",
+        "data":users
+    })))
+}
+
+#[get("/users/{user_id}")]
+async fn get_user_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_get_user_handler";
+    let user_id = path.into_inner();
+    let log_id = format!("{} Admin.Users.{}",chrono::Utc::now(),user_id);
+    user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_get_user.require_role", error))?;
+
+    let target = AdminServices::get_user(&log_id, user_id, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "admin_get_user.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get user success",
+        "data":target
+    })))
+}
+
+async fn set_disabled(
+    handler_name: &str,
+    user: AuthUser,
+    user_id: Uuid,
+    disabled: bool,
+    action_body: Json<AdminActionData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let action_data = json_validate(action_body)?;
+    let log_id = action_data.request_id.to_string();
+    let actor = user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_set_disabled.require_role", error))?;
+
+    let target = AdminServices::set_disabled(
+        &log_id,
+        actor,
+        user_id,
+        disabled,
+        action_data,
+        &app_state.db,
+        &app_state.redis,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "admin_set_disabled.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message": if disabled { "account disabled" } else { "account enabled" },
+        "data":target
+    })))
+}
+
+#[post("/users/{user_id}/disable")]
+async fn disable_user_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    action_body: Json<AdminActionData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    set_disabled("admin_disable_user_handler", user, path.into_inner(), true, action_body, app_state).await
+}
+
+#[post("/users/{user_id}/enable")]
+async fn enable_user_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    action_body: Json<AdminActionData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    set_disabled("admin_enable_user_handler", user, path.into_inner(), false, action_body, app_state).await
+}
+
+#[post("/users/{user_id}/logout")]
+async fn force_logout_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    action_body: Json<AdminActionData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_force_logout_handler";
+    let action_data = json_validate(action_body)?;
+    let log_id = action_data.request_id.to_string();
+    let actor = user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_force_logout.require_role", error))?;
+
+    let sessions = AdminServices::force_logout(
+        &log_id,
+        actor,
+        path.into_inner(),
+        action_data,
+        &app_state.db,
+        &app_state.redis,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "admin_force_logout.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"user logged out from all sessions",
+        "data":{
+            "sessions":sessions
+        }
+    })))
+}
+
+#[put("/users/{user_id}/roles")]
+async fn assign_roles_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    roles_body: Json<AssignRolesData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_assign_roles_handler";
+    let roles_data = json_validate(roles_body)?;
+    let log_id = roles_data.request_id.to_string();
+    let actor = user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_assign_roles.require_role", error))?;
+
+    let target = AdminServices::assign_roles(
+        &log_id,
+        actor,
+        path.into_inner(),
+        roles_data,
+        &app_state.db,
+        &app_state.redis,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "admin_assign_roles.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"roles updated",
+        "data":target
+    })))
+}
+
+pub fn admin_config(config:&mut ServiceConfig){
+    config.service(
+        scope("/admin")
+        .wrap(AccessTokenMW)
+        .service(list_users_handler)
+        .service(get_user_handler)
+        .service(disable_user_handler)
+        .service(enable_user_handler)
+        .service(force_logout_handler)
+        .service(assign_roles_handler)
+        .service(create_client_handler)
+        .service(list_clients_handler)
+        .service(delete_client_handler)
+    );
+}
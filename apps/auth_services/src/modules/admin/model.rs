@@ -0,0 +1,48 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize,Deserialize};
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug,Deserialize,Serialize,Clone)]
+pub struct UserListQuery{
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub search: Option<String>,
+    pub role: Option<String>,
+    pub disabled: Option<bool>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct AdminUserPayload{
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub phonenumber: Option<String>,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub roles: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct UserListPayload{
+    pub users: Vec<AdminUserPayload>,
+    pub page: i64,
+    pub limit: i64,
+    pub total: i64
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct AdminActionData{
+    pub request_id: String,
+    #[validate(length(max=500, message="too long"))]
+    pub reason: Option<String>
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct AssignRolesData{
+    pub request_id: String,
+    #[validate(length(max=20, message="too many roles"))]
+    pub roles: Vec<String>
+}
@@ -0,0 +1,190 @@
+use serde_json::Value;
+use sqlx::{query, query_as, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::error::AuthError;
+
+use super::model::{AdminUserPayload, UserListQuery};
+
+pub struct AdminQuery {}
+
+pub async fn insert_audit(
+    tx: &mut Transaction<'_, Postgres>,
+    actor_id: Uuid,
+    target_user_id: Option<Uuid>,
+    action: &str,
+    details: Value
+) -> Result<(), AuthError> {
+    query!(
+        r#"
+        INSERT INTO "admin_audit"
+        (actor_id, target_user_id, action, details) VALUES
+        ($1, $2, $3, $4)
+        "#,
+        actor_id,
+        target_user_id,
+        action,
+        details
+    ).execute(&mut **tx).await?;
+
+    Ok(())
+}
+
+impl AdminQuery {
+    pub async fn list_users(
+        filter: &UserListQuery,
+        limit: i64,
+        offset: i64,
+        db_pool: &PgPool
+    ) -> Result<(Vec<AdminUserPayload>, i64), AuthError> {
+        let users = query_as!(
+            AdminUserPayload,
+            r#"
+            SELECT u.id, u.email, u.username, u.phonenumber, u.email_verified, u.totp_enabled, u.created_at, u.disabled_at,
+            ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = u.id ORDER BY r.name) AS "roles!"
+            FROM "user" u
+            WHERE ($1::TEXT IS NULL OR u.email ILIKE '%' || $1 || '%' OR u.username ILIKE '%' || $1 || '%')
+            AND ($2::BOOLEAN IS NULL OR (u.disabled_at IS NOT NULL) = $2)
+            AND ($3::TEXT IS NULL OR EXISTS (SELECT 1 FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = u.id AND r.name = $3))
+            ORDER BY u.created_at DESC, u.id
+            LIMIT $4 OFFSET $5
+            "#,
+            filter.search,
+            filter.disabled,
+            filter.role,
+            limit,
+            offset
+        ).fetch_all(db_pool).await?;
+
+        let total = query!(
+            r#"
+            SELECT COUNT(*) AS "total!" FROM "user" u
+            WHERE ($1::TEXT IS NULL OR u.email ILIKE '%' || $1 || '%' OR u.username ILIKE '%' || $1 || '%')
+            AND ($2::BOOLEAN IS NULL OR (u.disabled_at IS NOT NULL) = $2)
+            AND ($3::TEXT IS NULL OR EXISTS (SELECT 1 FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = u.id AND r.name = $3))
+            "#,
+            filter.search,
+            filter.disabled,
+            filter.role
+        ).fetch_one(db_pool).await?;
+
+        Ok((users, total.total))
+    }
+
+    pub async fn find_user(
+        id: Uuid,
+        db_pool: &PgPool
+    ) -> Result<Option<AdminUserPayload>, AuthError> {
+        match query_as!(
+            AdminUserPayload,
+            r#"
+            SELECT u.id, u.email, u.username, u.phonenumber, u.email_verified, u.totp_enabled, u.created_at, u.disabled_at,
+            ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = u.id ORDER BY r.name) AS "roles!"
+            FROM "user" u WHERE u.id = $1
+            "#,
+            id
+        ).fetch_optional(db_pool).await{
+            Ok(user)=>Ok(user),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn set_user_disabled(
+        actor_id: Uuid,
+        user_id: Uuid,
+        disabled: bool,
+        details: Value,
+        db_pool: &PgPool
+    ) -> Result<bool, AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let result = query!(
+            r#"
+            UPDATE "user" SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, now()) ELSE NULL END
+            WHERE id = $1
+            "#,
+            user_id,
+            disabled
+        ).execute(&mut *tx).await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        let action = match disabled {
+            true => "user.disable",
+            false => "user.enable",
+        };
+        insert_audit(&mut tx, actor_id, Some(user_id), action, details).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn force_logout(
+        actor_id: Uuid,
+        user_id: Uuid,
+        details: Value,
+        db_pool: &PgPool
+    ) -> Result<u64, AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let result = query!(
+            r#"
+            DELETE FROM "refresh_token" WHERE userid = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        insert_audit(&mut tx, actor_id, Some(user_id), "user.force_logout", details).await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn replace_user_roles(
+        actor_id: Uuid,
+        user_id: Uuid,
+        roles: &[String],
+        details: Value,
+        db_pool: &PgPool
+    ) -> Result<(), AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let known = query!(
+            r#"
+            SELECT name FROM "role" WHERE name = ANY($1)
+            "#,
+            roles
+        ).fetch_all(&mut *tx).await?;
+
+        let unknown: Vec<&str> = roles.iter()
+            .filter(|role| !known.iter().any(|row| &row.name == *role))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(AuthError::validation(format!("unknown roles: {}", unknown.join(", "))));
+        }
+
+        query!(
+            r#"
+            DELETE FROM "user_role" WHERE user_id = $1
+            "#,
+            user_id
+        ).execute(&mut *tx).await?;
+
+        query!(
+            r#"
+            INSERT INTO "user_role" (user_id, role_id)
+            SELECT $1, id FROM "role" WHERE name = ANY($2)
+            "#,
+            user_id,
+            roles
+        ).execute(&mut *tx).await?;
+
+        insert_audit(&mut tx, actor_id, Some(user_id), "user.assign_roles", details).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
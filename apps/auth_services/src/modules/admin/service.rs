@@ -0,0 +1,159 @@
+use chrono::Utc;
+use jwt_libs::{keys::JwtKeyring, types::AccessToken};
+use logger_libs::Logger;
+use pgsql_libs::DbPool;
+use redis_libs::{revoke_subject_tokens, set_subject_disabled, RedisPool};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{error::AuthError, middlewares::authorization::ADMIN_ROLE};
+
+use super::{model::{AdminActionData, AdminUserPayload, AssignRolesData, UserListPayload, UserListQuery}, query::AdminQuery};
+
+pub struct AdminServices{}
+
+const DEFAULT_PAGE_SIZE: i64 = 20;
+const MAX_PAGE_SIZE: i64 = 100;
+
+// Access tokens carry roles and are otherwise valid until they expire, so every account change cuts them off.
+fn revoke_access_tokens(user_id: Uuid, redis_pool: &RedisPool, jwt_keyring: &JwtKeyring) -> Result<(), AuthError> {
+    revoke_subject_tokens(redis_pool, &user_id.to_string(), Utc::now().timestamp(), jwt_keyring.lifetimes().access_token)
+        .map_err(AuthError::Upstream)
+}
+
+impl AdminServices {
+    pub async fn list_users(
+        log_id: &str,
+        filter: UserListQuery,
+        db_pool: &DbPool
+    )-> Result<UserListPayload, AuthError>{
+        let handler_name = "admin_list_users_services";
+        let page = filter.page.unwrap_or(1).max(1);
+        let limit = filter.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let (users, total) = AdminQuery::list_users(&filter, limit, (page - 1) * limit, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_list_users_services.query_db", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "admin_list_users_services.query_db");
+        Ok(UserListPayload { users, page, limit, total })
+    }
+
+    pub async fn get_user(
+        log_id: &str,
+        user_id: Uuid,
+        db_pool: &DbPool
+    )-> Result<AdminUserPayload, AuthError>{
+        let handler_name = "admin_get_user_services";
+        match AdminQuery::find_user(user_id, db_pool).await{
+            Ok(Some(user))=>{
+                Logger::info_logger(handler_name, log_id, "admin_get_user_services.query_db");
+                Ok(user)
+            },
+            Ok(None)=>Err(AuthError::NotFound(String::from("user not found"))),
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "admin_get_user_services.query_db", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn set_disabled(
+        log_id: &str,
+        actor: &AccessToken,
+        user_id: Uuid,
+        disabled: bool,
+        data: AdminActionData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<AdminUserPayload, AuthError>{
+        let handler_name = "admin_set_disabled_services";
+
+        if disabled && actor.id == user_id {
+            return Err(AuthError::validation("you cannot disable your own account"));
+        }
+
+        let details = json!({ "reason": data.reason, "request_id": data.request_id });
+        if !AdminQuery::set_user_disabled(actor.id, user_id, disabled, details, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_set_disabled_services.update_user", &error.to_string());
+        })? {
+            return Err(AuthError::NotFound(String::from("user not found")));
+        }
+
+        set_subject_disabled(redis_pool, &user_id.to_string(), disabled).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_set_disabled_services.set_subject_disabled", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        if disabled {
+            AdminQuery::force_logout(actor.id, user_id, json!({ "reason": "account disabled", "request_id": data.request_id }), db_pool).await.inspect_err(|error|{
+                Logger::warning_logger(handler_name, log_id, "admin_set_disabled_services.delete_refresh_tokens", &error.to_string());
+            })?;
+            revoke_access_tokens(user_id, redis_pool, jwt_keyring).inspect_err(|error|{
+                Logger::warning_logger(handler_name, log_id, "admin_set_disabled_services.revoke_tokens", &error.to_string());
+            })?;
+        }
+
+        Logger::info_logger(handler_name, log_id, "admin_set_disabled_services.success");
+        Self::get_user(log_id, user_id, db_pool).await
+    }
+
+    pub async fn force_logout(
+        log_id: &str,
+        actor: &AccessToken,
+        user_id: Uuid,
+        data: AdminActionData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<u64, AuthError>{
+        let handler_name = "admin_force_logout_services";
+        Self::get_user(log_id, user_id, db_pool).await?;
+
+        let sessions = AdminQuery::force_logout(actor.id, user_id, json!({ "reason": data.reason, "request_id": data.request_id }), db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_force_logout_services.delete_refresh_tokens", &error.to_string());
+        })?;
+
+        revoke_access_tokens(user_id, redis_pool, jwt_keyring).inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_force_logout_services.revoke_tokens", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "admin_force_logout_services.success");
+        Ok(sessions)
+    }
+
+    pub async fn assign_roles(
+        log_id: &str,
+        actor: &AccessToken,
+        user_id: Uuid,
+        data: AssignRolesData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<AdminUserPayload, AuthError>{
+        let handler_name = "admin_assign_roles_services";
+        let mut roles: Vec<String> = data.roles.iter().map(|role| role.trim().to_lowercase()).collect();
+        roles.sort();
+        roles.dedup();
+
+        if actor.id == user_id && !roles.iter().any(|role| role == ADMIN_ROLE) {
+            return Err(AuthError::validation("you cannot remove your own admin role"));
+        }
+
+        let previous = Self::get_user(log_id, user_id, db_pool).await?;
+        let details = json!({ "previous": previous.roles, "roles": roles, "request_id": data.request_id });
+
+        AdminQuery::replace_user_roles(actor.id, user_id, &roles, details, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_assign_roles_services.replace_roles", &error.to_string());
+        })?;
+
+        revoke_access_tokens(user_id, redis_pool, jwt_keyring).inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "admin_assign_roles_services.revoke_tokens", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "admin_assign_roles_services.success");
+        Self::get_user(log_id, user_id, db_pool).await
+    }
+}
@@ -0,0 +1,126 @@
+use std::{sync::Arc, time::Duration};
+
+use futures::StreamExt;
+use lapin::{
+    options::{BasicAckOptions, BasicConsumeOptions, BasicNackOptions, QueueDeclareOptions},
+    types::FieldTable,
+};
+use logger_libs::Logger;
+use rabbitmq_libs::RabbitMqPool;
+use serde::de::DeserializeOwned;
+
+use crate::{config_type::Mail, modules::user::model::{PasswordResetMessage, RegisterMessage}};
+
+use super::sender::{MailMessage, MailSender};
+
+pub const REGISTER_QUEUE: &str = "register_queue";
+pub const PASSWORD_RESET_QUEUE: &str = "password_reset_queue";
+
+pub fn verification_mail(config: &Mail, message: &RegisterMessage) -> MailMessage {
+    MailMessage {
+        from: config.from.clone(),
+        to: message.email.clone(),
+        subject: String::from("Verify your email address"),
+        body: format!(
+            "Hi {},\r\n\r\nPlease confirm your email address by opening the link below:\r\n\r\n{}?token={}\r\n\r\nThe link expires in {} hours.\r\n",
+            message.username,
+            config.verify_url,
+            message.verification_token,
+            config.verification_token_lifetime / 3600
+        ),
+    }
+}
+
+pub fn password_reset_mail(config: &Mail, message: &PasswordResetMessage) -> MailMessage {
+    MailMessage {
+        from: config.from.clone(),
+        to: message.email.clone(),
+        subject: String::from("Reset your password"),
+        body: format!(
+            "Hi {},\r\n\r\nSomeone asked to reset the password of your account. If it was you, open the link below:\r\n\r\n{}?token={}\r\n\r\nThe link expires in {} minutes and can be used once. If you did not ask for this, you can ignore this email.\r\n",
+            message.username,
+            config.reset_url,
+            message.reset_token,
+            config.reset_token_lifetime / 60
+        ),
+    }
+}
+
+async fn consume_queue<T: DeserializeOwned>(
+    queue: &str,
+    render: fn(&Mail, &T) -> MailMessage,
+    rabbit_pool: &RabbitMqPool,
+    config: &Mail,
+    sender: &Arc<dyn MailSender>,
+) -> Result<(), String> {
+    let handler_name = "mail_consumer";
+
+    let conn = rabbit_pool.get().await.map_err(|error| format!("RabbitMQ connection error: {}", error))?;
+    let channel = conn.create_channel().await.map_err(|error| format!("RabbitMQ channel error: {}", error))?;
+
+    channel
+        .queue_declare(queue, QueueDeclareOptions::default(), FieldTable::default())
+        .await
+        .map_err(|error| format!("RabbitMQ queue declare error: {}", error))?;
+
+    let mut consumer = channel
+        .basic_consume(queue, &format!("auth_services.{}", queue), BasicConsumeOptions::default(), FieldTable::default())
+        .await
+        .map_err(|error| format!("RabbitMQ consume error: {}", error))?;
+
+    Logger::info_logger(handler_name, queue, "mail_consumer.consume");
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery.map_err(|error| format!("RabbitMQ delivery error: {}", error))?;
+
+        let message: T = match serde_json::from_slice(&delivery.data) {
+            Ok(message) => message,
+            Err(error) => {
+                Logger::warning_logger(handler_name, queue, "mail_consumer.parse_message", &error.to_string());
+                let _ = delivery.nack(BasicNackOptions { requeue: false, ..Default::default() }).await;
+                continue;
+            }
+        };
+
+        let mail = render(config, &message);
+        let log_id = format!("{}.{}", queue, mail.to);
+
+        match sender.send(&mail) {
+            Ok(_) => {
+                Logger::info_logger(handler_name, &log_id, "mail_consumer.send_mail");
+                let _ = delivery.ack(BasicAckOptions::default()).await;
+            },
+            Err(error) => {
+                Logger::warning_logger(handler_name, &log_id, "mail_consumer.send_mail", &error);
+                actix_web::rt::time::sleep(Duration::from_secs(1)).await;
+                let _ = delivery.nack(BasicNackOptions { requeue: true, ..Default::default() }).await;
+            }
+        }
+    }
+
+    Err(String::from("RabbitMQ consumer closed"))
+}
+
+fn spawn_consumer<T: DeserializeOwned + 'static>(
+    queue: &'static str,
+    render: fn(&Mail, &T) -> MailMessage,
+    rabbit_pool: RabbitMqPool,
+    config: Mail,
+    sender: Arc<dyn MailSender>,
+) {
+    let handler_name = "mail_consumer";
+
+    actix_web::rt::spawn(async move {
+        loop {
+            if let Err(error) = consume_queue(queue, render, &rabbit_pool, &config, &sender).await {
+                Logger::warning_logger(handler_name, queue, "mail_consumer.restart", &error);
+            }
+            actix_web::rt::time::sleep(Duration::from_secs(5)).await;
+        }
+    });
+}
+
+pub fn spawn_mail_consumers(rabbit_pool: RabbitMqPool, config: Mail, sender: Arc<dyn MailSender>) {
+    spawn_consumer(REGISTER_QUEUE, verification_mail, rabbit_pool.clone(), config.clone(), sender.clone());
+    spawn_consumer(PASSWORD_RESET_QUEUE, password_reset_mail, rabbit_pool, config, sender);
+}
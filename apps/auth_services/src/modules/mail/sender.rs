@@ -0,0 +1,74 @@
+use std::{fs, path::PathBuf, sync::Arc};
+
+use chrono::Utc;
+use logger_libs::Logger;
+use serde::{Deserialize, Serialize};
+
+use crate::config_type::Mail;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MailMessage {
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+impl MailMessage {
+    pub fn render(&self) -> String {
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from,
+            self.to,
+            self.subject,
+            Utc::now().to_rfc2822(),
+            self.body
+        )
+    }
+}
+
+pub trait MailSender: Send + Sync {
+    fn send(&self, message: &MailMessage) -> Result<(), String>;
+}
+
+pub struct StdoutMailSender;
+
+impl MailSender for StdoutMailSender {
+    fn send(&self, message: &MailMessage) -> Result<(), String> {
+        println!("{}", message.render());
+        Ok(())
+    }
+}
+
+pub struct FileMailSender {
+    directory: PathBuf,
+}
+
+impl FileMailSender {
+    pub fn new(directory: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&directory).map_err(|error| format!("failed to create mail directory {}: {}", directory.display(), error))?;
+        Ok(FileMailSender { directory })
+    }
+}
+
+impl MailSender for FileMailSender {
+    fn send(&self, message: &MailMessage) -> Result<(), String> {
+        let file_name = format!("{}_{}.eml", Utc::now().format("%Y%m%dT%H%M%S%.6f"), message.to.replace(['/', '\\'], "_"));
+        let path = self.directory.join(file_name);
+
+        fs::write(&path, message.render()).map_err(|error| format!("failed to write mail {}: {}", path.display(), error))?;
+        Logger::info_logger("mail_sender.file", &message.to, &format!("mail_sender.write.{}", path.display()));
+        Ok(())
+    }
+}
+
+pub fn mail_sender(config: &Mail) -> Result<Arc<dyn MailSender>, String> {
+    match config.sender.as_str() {
+        "stdout" => Ok(Arc::new(StdoutMailSender)),
+        "file" => match &config.file_path {
+            Some(path) => Ok(Arc::new(FileMailSender::new(PathBuf::from(path))?)),
+            None => Err(String::from("mail.file_path is required for the file sender")),
+        },
+        other => Err(format!("unsupported mail sender: {}", other)),
+    }
+}
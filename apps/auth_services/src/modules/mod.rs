@@ -1 +1,6 @@
+pub mod admin;
+pub mod mail;
+pub mod mfa;
+pub mod oauth;
+pub mod oidc;
 pub mod user;
\ No newline at end of file
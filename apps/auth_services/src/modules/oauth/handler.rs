@@ -0,0 +1,47 @@
+use actix_web::{get, http::header::LOCATION, web::{Data, Path, Query}, HttpRequest, HttpResponse};
+use logger_libs::Logger;
+
+use crate::{error::{log_failure, AuthError}, modules::user::handler::{login_response, session_metadata}, AppState};
+
+use super::{model::OAuthCallbackQuery, service::OAuthServices};
+
+#[get("/oauth/{provider}/start")]
+pub async fn oauth_start_handler(
+    path: Path<String>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oauth_start_handler";
+    let provider_name = path.into_inner();
+    let log_id = format!("{} OAuth.{}",chrono::Utc::now(),provider_name);
+
+    let provider = app_state.identity_providers.get(&provider_name)
+        .map_err(|error| log_failure(handler_name, &log_id, "oauth_start.provider", error))?;
+    let authorization_url = OAuthServices::start(&log_id, provider, app_state.oauth.state_lifetime, &app_state.redis)
+        .map_err(|error| log_failure(handler_name, &log_id, "oauth_start.failed", error))?;
+
+    Logger::info_logger(handler_name, &log_id, "oauth_start.redirect");
+    Ok(HttpResponse::Found().insert_header((LOCATION, authorization_url)).finish())
+}
+
+#[get("/oauth/{provider}/callback")]
+pub async fn oauth_callback_handler(
+    req: HttpRequest,
+    path: Path<String>,
+    callback: Query<OAuthCallbackQuery>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oauth_callback_handler";
+    let provider_name = path.into_inner();
+    let log_id = format!("{} OAuth.{}",chrono::Utc::now(),provider_name);
+
+    let provider = app_state.identity_providers.get(&provider_name)
+        .map_err(|error| log_failure(handler_name, &log_id, "oauth_callback.provider", error))?;
+    let identity = OAuthServices::complete(&log_id, provider, callback.into_inner(), &app_state.redis).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oauth_callback.complete", error))?;
+
+    let result = OAuthServices::login(&log_id, identity, &session_metadata(&req), app_state.mail.verification_token_lifetime, &app_state.db, &app_state.rabbit, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oauth_callback.login", error))?;
+
+    Logger::info_logger(handler_name, &log_id, "oauth_callback.success");
+    Ok(login_response(result))
+}
@@ -0,0 +1,37 @@
+use serde::{Serialize,Deserialize};
+use uuid::Uuid;
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct OAuthCallbackQuery{
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize,PartialEq,Eq)]
+pub struct OAuthState{
+    pub provider: String,
+    pub code_verifier: String
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ExternalIdentity{
+    pub provider: String,
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub username: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct IdentityLoginRow{
+    pub id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub disabled: bool,
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>
+}
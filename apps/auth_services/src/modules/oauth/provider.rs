@@ -0,0 +1,255 @@
+use std::{collections::HashMap, sync::Arc};
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use async_trait::async_trait;
+use data_encoding::BASE64URL_NOPAD;
+use reqwest::{header::{ACCEPT, USER_AGENT}, Client, Url};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{config_type::OAuthProviderConfig, error::AuthError};
+
+use super::model::ExternalIdentity;
+
+// Authorization code flow with PKCE (S256); implementations only differ in how they talk to the provider.
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    fn name(&self) -> &str;
+    fn authorization_url(&self, state: &str, code_challenge: &str) -> String;
+    async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<ExternalIdentity, AuthError>;
+}
+
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+pub fn code_challenge(code_verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+fn claim_string(claims: &Value, field: &str) -> Option<String> {
+    match claims.get(field)? {
+        Value::String(value) if !value.is_empty() => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn claim_bool(claims: &Value, field: &str) -> bool {
+    match claims.get(field) {
+        Some(Value::Bool(value)) => *value,
+        Some(Value::String(value)) => value == "true",
+        _ => false,
+    }
+}
+
+fn upstream(provider: &str, step: &str, error: impl std::fmt::Display) -> AuthError {
+    AuthError::Upstream(format!("{} {} failed: {}", provider, step, error))
+}
+
+// Config-driven provider for anything that speaks plain OAuth2 and exposes a JSON userinfo endpoint.
+pub struct OAuth2Provider {
+    name: String,
+    config: OAuthProviderConfig,
+    authorization_url: Url,
+    client: Client,
+}
+
+impl OAuth2Provider {
+    pub fn new(name: &str, config: OAuthProviderConfig) -> Result<Self, String> {
+        let authorization_url = Url::parse(&config.authorization_url)
+            .map_err(|error| format!("invalid authorization_url for {}: {}", name, error))?;
+        for url in [&config.token_url, &config.userinfo_url, &config.redirect_uri] {
+            Url::parse(url).map_err(|error| format!("invalid url {} for {}: {}", url, name, error))?;
+        }
+
+        Ok(Self { name: name.to_string(), config, authorization_url, client: Client::new() })
+    }
+
+    pub fn identity_from_claims(&self, claims: &Value) -> Result<ExternalIdentity, AuthError> {
+        let subject = claim_string(claims, &self.config.subject_field)
+            .ok_or_else(|| upstream(&self.name, "userinfo", "response has no subject"))?;
+
+        Ok(ExternalIdentity {
+            provider: self.name.clone(),
+            subject,
+            email: claim_string(claims, &self.config.email_field).map(|email| email.trim().to_string()),
+            email_verified: self.config.email_verified_field.as_deref().is_some_and(|field| claim_bool(claims, field)),
+            username: self.config.username_field.as_deref().and_then(|field| claim_string(claims, field)),
+        })
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for OAuth2Provider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn authorization_url(&self, state: &str, code_challenge: &str) -> String {
+        let mut url = self.authorization_url.clone();
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_uri)
+            .append_pair("scope", &self.config.scopes.join(" "))
+            .append_pair("state", state)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        url.to_string()
+    }
+
+    async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<ExternalIdentity, AuthError> {
+        let token: Value = self.client.post(&self.config.token_url)
+            .header(ACCEPT, "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_uri),
+                ("client_id", &self.config.client_id),
+                ("client_secret", &self.config.client_secret),
+                ("code_verifier", code_verifier),
+            ])
+            .send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|error| upstream(&self.name, "token exchange", error))?
+            .json().await
+            .map_err(|error| upstream(&self.name, "token exchange", error))?;
+
+        let access_token = claim_string(&token, "access_token")
+            .ok_or_else(|| upstream(&self.name, "token exchange", "response has no access_token"))?;
+
+        let claims: Value = self.client.get(&self.config.userinfo_url)
+            .bearer_auth(access_token)
+            .header(ACCEPT, "application/json")
+            .header(USER_AGENT, "auth_services")
+            .send().await
+            .and_then(|response| response.error_for_status())
+            .map_err(|error| upstream(&self.name, "userinfo", error))?
+            .json().await
+            .map_err(|error| upstream(&self.name, "userinfo", error))?;
+
+        self.identity_from_claims(&claims)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct IdentityProviders {
+    providers: HashMap<String, Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    // Providers without a client_id are left out so the sample config can ship them unconfigured.
+    pub fn from_config(configs: &HashMap<String, OAuthProviderConfig>) -> Result<Self, String> {
+        let mut providers: HashMap<String, Arc<dyn IdentityProvider>> = HashMap::new();
+        for (name, config) in configs.iter().filter(|(_, config)| !config.client_id.is_empty()) {
+            providers.insert(name.clone(), Arc::new(OAuth2Provider::new(name, config.clone())?));
+        }
+        Ok(Self { providers })
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn IdentityProvider, AuthError> {
+        self.providers.get(name)
+            .map(|provider| provider.as_ref())
+            .ok_or_else(|| AuthError::NotFound(format!("unknown identity provider: {}", name)))
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    pub const MOCK_CODE: &str = "mock-authorization-code";
+
+    // Accepts MOCK_CODE only when the verifier matches the challenge sent in the last authorization_url.
+    pub struct MockProvider {
+        pub identity: ExternalIdentity,
+        challenge: Mutex<Option<String>>,
+    }
+
+    impl MockProvider {
+        pub fn new(identity: ExternalIdentity) -> Self {
+            Self { identity, challenge: Mutex::new(None) }
+        }
+    }
+
+    #[async_trait]
+    impl IdentityProvider for MockProvider {
+        fn name(&self) -> &str {
+            &self.identity.provider
+        }
+
+        fn authorization_url(&self, state: &str, code_challenge: &str) -> String {
+            *self.challenge.lock().unwrap() = Some(code_challenge.to_string());
+            format!("https://mock.test/authorize?state={}&code_challenge={}", state, code_challenge)
+        }
+
+        async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<ExternalIdentity, AuthError> {
+            let expected = self.challenge.lock().unwrap().clone();
+            if code != MOCK_CODE || expected != Some(code_challenge(code_verifier)) {
+                return Err(AuthError::Unauthorized(String::from("invalid authorization code")));
+            }
+            Ok(self.identity.clone())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn github() -> OAuthProviderConfig {
+        OAuthProviderConfig {
+            client_id: String::from("client"),
+            client_secret: String::from("secret"),
+            authorization_url: String::from("https://github.com/login/oauth/authorize"),
+            token_url: String::from("https://github.com/login/oauth/access_token"),
+            userinfo_url: String::from("https://api.github.com/user"),
+            redirect_uri: String::from("http://localhost:8080/api/auth/oauth/github/callback"),
+            scopes: vec![String::from("read:user"), String::from("user:email")],
+            subject_field: String::from("id"),
+            email_field: String::from("email"),
+            email_verified_field: None,
+            username_field: Some(String::from("login")),
+        }
+    }
+
+    #[test]
+    fn pkce_challenge_is_unpadded_s256() {
+        assert_eq!(code_challenge("dBjftJeZ4CK-1GnNxcmwB_mZU4YmUsLTWN8TUJqC0l4"), "TJT6u_FkFcF5xdPbOWm3baeXaPtnn-kwN-_Fy1H1IDY");
+        assert_eq!(random_token().len(), 43);
+    }
+
+    #[test]
+    fn builds_authorization_url_and_reads_claims() {
+        let provider = OAuth2Provider::new("github", github()).unwrap();
+        let url = Url::parse(&provider.authorization_url("state-1", "challenge-1")).unwrap();
+        let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["client_id"], "client");
+        assert_eq!(params["scope"], "read:user user:email");
+        assert_eq!(params["state"], "state-1");
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let identity = provider.identity_from_claims(&json!({ "id": 42, "login": "octo", "email": "octo@mail.com" })).unwrap();
+        assert_eq!(identity.subject, "42");
+        assert_eq!(identity.username.as_deref(), Some("octo"));
+        assert!(!identity.email_verified);
+        assert!(provider.identity_from_claims(&json!({ "login": "octo" })).is_err());
+    }
+
+    #[test]
+    fn skips_unconfigured_providers() {
+        let mut configs = HashMap::from([(String::from("github"), github())]);
+        configs.insert(String::from("google"), OAuthProviderConfig::default());
+
+        let providers = IdentityProviders::from_config(&configs).unwrap();
+        assert!(providers.get("github").is_ok());
+        assert!(providers.get("google").is_err());
+    }
+}
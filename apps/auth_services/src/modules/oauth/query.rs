@@ -0,0 +1,120 @@
+use sqlx::{query, query_as, PgPool};
+use uuid::Uuid;
+
+use crate::error::AuthError;
+
+use super::model::{ExternalIdentity, IdentityLoginRow};
+
+pub struct OAuthQuery {}
+
+impl OAuthQuery {
+    pub async fn touch_identity(
+        provider: &str,
+        subject: &str,
+        db_pool: &PgPool
+    ) -> Result<Option<Uuid>, AuthError> {
+        match query!(
+            r#"
+            UPDATE "user_identity" SET last_login_at = now()
+            WHERE provider = $1 AND subject = $2
+            RETURNING user_id
+            "#,
+            provider,
+            subject
+        ).fetch_optional(db_pool).await{
+            Ok(row)=>Ok(row.map(|row| row.user_id)),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn link_identity(
+        user_id: Uuid,
+        identity: &ExternalIdentity,
+        db_pool: &PgPool
+    ) -> Result<(), AuthError> {
+        match query!(
+            r#"
+            INSERT INTO "user_identity"
+            (user_id, provider, subject, email) VALUES
+            ($1, $2, $3, $4)
+            "#,
+            user_id,
+            identity.provider,
+            identity.subject,
+            identity.email
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn create_user_with_identity(
+        email: &str,
+        username: &str,
+        identity: &ExternalIdentity,
+        db_pool: &PgPool
+    ) -> Result<Uuid, AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let user = query!(
+            r#"
+            INSERT INTO "user"
+            (email, username, email_verified) VALUES
+            ($1, $2, $3)
+            RETURNING id
+            "#,
+            email,
+            username,
+            identity.email_verified
+        ).fetch_one(&mut *tx).await?;
+
+        query!(
+            r#"
+            INSERT INTO "user_identity"
+            (user_id, provider, subject, email) VALUES
+            ($1, $2, $3, $4)
+            "#,
+            user.id,
+            identity.provider,
+            identity.subject,
+            identity.email
+        ).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        Ok(user.id)
+    }
+
+    pub async fn username_taken(
+        username: &str,
+        db_pool: &PgPool
+    ) -> Result<bool, AuthError> {
+        match query!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM "user" WHERE username = $1) AS "taken!"
+            "#,
+            username
+        ).fetch_one(db_pool).await{
+            Ok(row)=>Ok(row.taken),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_login_user(
+        user_id: Uuid,
+        db_pool: &PgPool
+    ) -> Result<Option<IdentityLoginRow>, AuthError> {
+        match query_as!(
+            IdentityLoginRow,
+            r#"
+            SELECT id, email, username, email_verified, totp_enabled, disabled_at IS NOT NULL AS "disabled!",
+            ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = "user".id ORDER BY r.name) AS "roles!",
+            ARRAY(SELECT DISTINCT p.name FROM "user_role" ur JOIN "role_permission" rp ON rp.role_id = ur.role_id JOIN "permission" p ON p.id = rp.permission_id WHERE ur.user_id = "user".id ORDER BY p.name) AS "permissions!"
+            FROM "user" WHERE id = $1
+            "#,
+            user_id
+        ).fetch_optional(db_pool).await{
+            Ok(user)=>Ok(user),
+            Err(error)=>Err(error.into())
+        }
+    }
+}
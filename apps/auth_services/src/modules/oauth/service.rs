@@ -0,0 +1,254 @@
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use jwt_libs::{keys::JwtKeyring, types::AccessToken};
+use logger_libs::Logger;
+use pgsql_libs::DbPool;
+use rabbitmq_libs::RabbitMqPool;
+use redis_libs::{store_one_time, take_one_time, RedisPool};
+use uuid::Uuid;
+
+use crate::{error::AuthError, modules::user::{model::{LoginResult, SessionMetadata}, query::UserQuery, service::UserServices}};
+
+use super::{model::{ExternalIdentity, OAuthCallbackQuery, OAuthState}, provider::{code_challenge, random_token, IdentityProvider}, query::OAuthQuery};
+
+pub struct OAuthServices{}
+
+const OAUTH_STATE_PREFIX: &str = "oauth_state";
+const USERNAME_MIN_LENGTH: usize = 5;
+const USERNAME_MAX_LENGTH: usize = 24;
+const USERNAME_ATTEMPTS: usize = 5;
+
+fn state_key(state: &str) -> String {
+    format!("{}:{}", OAUTH_STATE_PREFIX, state)
+}
+
+fn username_base(identity: &ExternalIdentity, email: &str) -> String {
+    let source = identity.username.as_deref().unwrap_or_else(|| email.split('@').next().unwrap_or_default());
+    let mut base: String = source.to_lowercase().chars()
+        .filter(|char| char.is_ascii_alphanumeric() || *char == '_')
+        .take(USERNAME_MAX_LENGTH)
+        .collect();
+    while base.len() < USERNAME_MIN_LENGTH {
+        base.push('_');
+    }
+    base
+}
+
+impl OAuthServices {
+    pub fn start(
+        log_id: &str,
+        provider: &dyn IdentityProvider,
+        state_lifetime: i64,
+        redis_pool: &RedisPool
+    ) -> Result<String, AuthError> {
+        let handler_name = "oauth_start_services";
+        let state = random_token();
+        let oauth_state = OAuthState { provider: provider.name().to_string(), code_verifier: random_token() };
+        let value = serde_json::to_string(&oauth_state).map_err(|error| AuthError::Internal(format!("error serialize oauth state: {}", error)))?;
+
+        store_one_time(redis_pool, &state_key(&state), &value, state_lifetime).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oauth_start_services.store_state", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oauth_start_services.store_state");
+        Ok(provider.authorization_url(&state, &code_challenge(&oauth_state.code_verifier)))
+    }
+
+    // The state is consumed before anything else so a callback can never be replayed, even a failed one.
+    pub async fn complete(
+        log_id: &str,
+        provider: &dyn IdentityProvider,
+        callback: OAuthCallbackQuery,
+        redis_pool: &RedisPool
+    ) -> Result<ExternalIdentity, AuthError> {
+        let handler_name = "oauth_complete_services";
+        let state = callback.state.ok_or_else(|| AuthError::validation("missing state"))?;
+
+        let stored = take_one_time(redis_pool, &state_key(&state)).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oauth_complete_services.take_state", &error);
+            AuthError::Upstream(error)
+        })?;
+        let oauth_state: OAuthState = match stored.as_deref().map(serde_json::from_str) {
+            Some(Ok(oauth_state)) => oauth_state,
+            _ => return Err(AuthError::Unauthorized(String::from("invalid or expired oauth state"))),
+        };
+        if oauth_state.provider != provider.name() {
+            return Err(AuthError::Unauthorized(String::from("oauth state issued for another provider")));
+        }
+
+        if let Some(error) = callback.error {
+            let description = callback.error_description.unwrap_or_default();
+            return Err(AuthError::Unauthorized(format!("provider denied authorization: {} {}", error, description).trim_end().to_string()));
+        }
+        let code = callback.code.ok_or_else(|| AuthError::validation("missing code"))?;
+
+        let identity = provider.exchange_code(&code, &oauth_state.code_verifier).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oauth_complete_services.exchange_code", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oauth_complete_services.exchange_code");
+        Ok(identity)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn login(
+        log_id: &str,
+        identity: ExternalIdentity,
+        metadata: &SessionMetadata,
+        verification_token_lifetime: i64,
+        db_pool: &DbPool,
+        rabbit_pool: &RabbitMqPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<LoginResult, AuthError> {
+        let handler_name = "oauth_login_services";
+        let (user_id, created) = Self::resolve_user(log_id, &identity, db_pool).await?;
+
+        let user = match OAuthQuery::find_login_user(user_id, db_pool).await? {
+            Some(user) => user,
+            None => return Err(AuthError::NotFound(String::from("user not found"))),
+        };
+
+        if user.disabled {
+            let error = AuthError::Forbidden(String::from("account disabled"));
+            Logger::warning_logger(handler_name, log_id, "oauth_login_services.account_disabled", &error.to_string());
+            return Err(error);
+        }
+
+        // Same rule as password login; an account created from an unverified provider email gets the verification mail.
+        if !user.email_verified {
+            if created {
+                match UserServices::send_verification_email(log_id, user.id, &user.email, &user.username, verification_token_lifetime, db_pool, rabbit_pool).await {
+                    Ok(_) => Logger::info_logger(handler_name, log_id, "oauth_login_services.send_verification"),
+                    Err(error) => Logger::warning_logger(handler_name, log_id, "oauth_login_services.send_verification", &error.to_string()),
+                }
+            }
+
+            let error = AuthError::Forbidden(String::from("email not verified"));
+            Logger::warning_logger(handler_name, log_id, "oauth_login_services.email_verified", &error.to_string());
+            return Err(error);
+        }
+
+        Logger::info_logger(handler_name, log_id, "oauth_login_services.resolve_user");
+        let access_token = AccessToken {
+            id: user.id,
+            email: user.email,
+            username: user.username,
+            roles: user.roles,
+            permissions: user.permissions,
+        };
+        UserServices::complete_login(log_id, access_token, user.totp_enabled, metadata, db_pool, jwt_keyring).await
+    }
+
+    // Known identity first, then an existing account with the same provider-verified email, otherwise a new account.
+    // The flag tells whether the account was created by this call.
+    async fn resolve_user(
+        log_id: &str,
+        identity: &ExternalIdentity,
+        db_pool: &DbPool
+    ) -> Result<(Uuid, bool), AuthError> {
+        let handler_name = "oauth_resolve_user_services";
+        if let Some(user_id) = OAuthQuery::touch_identity(&identity.provider, &identity.subject, db_pool).await? {
+            return Ok((user_id, false));
+        }
+
+        let email = identity.email.as_deref()
+            .ok_or_else(|| AuthError::validation("identity provider did not share an email address"))?;
+
+        if let Some(existing) = UserQuery::find_user_by_email(email, db_pool).await? {
+            if !identity.email_verified {
+                return Err(AuthError::Conflict(String::from("an account with this email already exists, sign in with your password first")));
+            }
+            OAuthQuery::link_identity(existing.id, identity, db_pool).await.inspect_err(|error|{
+                Logger::warning_logger(handler_name, log_id, "oauth_resolve_user_services.link_identity", &error.to_string());
+            })?;
+            Logger::info_logger(handler_name, log_id, "oauth_resolve_user_services.link_identity");
+            return Ok((existing.id, false));
+        }
+
+        let username = Self::unique_username(identity, email, db_pool).await?;
+        let user_id = OAuthQuery::create_user_with_identity(email, &username, identity, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oauth_resolve_user_services.create_user", &error.to_string());
+        })?;
+        Logger::info_logger(handler_name, log_id, "oauth_resolve_user_services.create_user");
+        Ok((user_id, true))
+    }
+
+    async fn unique_username(
+        identity: &ExternalIdentity,
+        email: &str,
+        db_pool: &DbPool
+    ) -> Result<String, AuthError> {
+        let base = username_base(identity, email);
+        if !OAuthQuery::username_taken(&base, db_pool).await? {
+            return Ok(base);
+        }
+
+        for _ in 0..USERNAME_ATTEMPTS {
+            let candidate = format!("{}_{:04x}", base, OsRng.next_u32() as u16);
+            if !OAuthQuery::username_taken(&candidate, db_pool).await? {
+                return Ok(candidate);
+            }
+        }
+        Err(AuthError::Conflict(String::from("could not pick a free username, register with a password instead")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis_libs::testing::test_redis_pool;
+    use reqwest::Url;
+
+    use crate::modules::oauth::provider::mock::{MockProvider, MOCK_CODE};
+
+    use super::*;
+
+    fn identity(provider: &str) -> ExternalIdentity {
+        ExternalIdentity {
+            provider: provider.to_string(),
+            subject: String::from("subject-1"),
+            email: Some(String::from("Jane.Doe@mail.com")),
+            email_verified: true,
+            username: None,
+        }
+    }
+
+    fn callback(url: &str, code: &str) -> OAuthCallbackQuery {
+        let state = Url::parse(url).unwrap().query_pairs().find(|(key, _)| key == "state").map(|(_, value)| value.into_owned());
+        OAuthCallbackQuery { code: Some(code.to_string()), state, error: None, error_description: None }
+    }
+
+    #[actix_web::test]
+    async fn completes_flow_once_with_matching_verifier() {
+        let redis_pool = test_redis_pool();
+        let provider = MockProvider::new(identity("mock"));
+
+        let url = OAuthServices::start("test", &provider, 600, &redis_pool).unwrap();
+        let result = OAuthServices::complete("test", &provider, callback(&url, MOCK_CODE), &redis_pool).await.unwrap();
+        assert_eq!(result, identity("mock"));
+
+        let replay = OAuthServices::complete("test", &provider, callback(&url, MOCK_CODE), &redis_pool).await;
+        assert!(matches!(replay, Err(AuthError::Unauthorized(_))));
+    }
+
+    #[actix_web::test]
+    async fn rejects_foreign_state_and_bad_code() {
+        let redis_pool = test_redis_pool();
+        let provider = MockProvider::new(identity("mock"));
+        let other = MockProvider::new(identity("other"));
+
+        let url = OAuthServices::start("test", &provider, 600, &redis_pool).unwrap();
+        let foreign = OAuthServices::complete("test", &other, callback(&url, MOCK_CODE), &redis_pool).await;
+        assert!(matches!(foreign, Err(AuthError::Unauthorized(_))));
+
+        let url = OAuthServices::start("test", &provider, 600, &redis_pool).unwrap();
+        let bad_code = OAuthServices::complete("test", &provider, callback(&url, "guessed"), &redis_pool).await;
+        assert!(matches!(bad_code, Err(AuthError::Unauthorized(_))));
+    }
+
+    #[test]
+    fn derives_username_from_identity() {
+        assert_eq!(username_base(&identity("mock"), "Jane.Doe@mail.com"), "janedoe");
+        let short = ExternalIdentity { username: Some(String::from("Al")), ..identity("mock") };
+        assert_eq!(username_base(&short, "al@mail.com"), "al___");
+    }
+}
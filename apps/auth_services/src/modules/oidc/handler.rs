@@ -0,0 +1,411 @@
+use actix_web::{cookie::{time::Duration as CookieDuration, Cookie, SameSite}, delete, get, http::header::{AUTHORIZATION, CACHE_CONTROL, LOCATION, PRAGMA}, post, web::{scope, Data, Form, Json, Path, Query, ServiceConfig}, HttpRequest, HttpResponse};
+use data_encoding::BASE64;
+use logger_libs::Logger;
+use serde_json::json;
+
+use crate::{error::{log_failure, AuthError, OAuthError}, middlewares::{access_token_middleware::{bearer_token, AccessTokenMW}, authorization::{AuthUser, ADMIN_ROLE}}, modules::{admin::model::AdminActionData, user::handler::{access_claims, json_validate}}, AppState};
+
+use super::{model::{AuthorizeQuery, ClientCredentials, ConsentDecision, CreateClientData, TokenRequest}, service::{OidcServices, SESSION_COOKIE}};
+
+// client_secret_basic or client_secret_post; a bare client_id in the form is a public client.
+fn client_credentials(req: &HttpRequest, request: &TokenRequest) -> Result<ClientCredentials, OAuthError> {
+    let basic = req.headers().get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "));
+
+    match (basic, &request.client_id) {
+        (Some(encoded), None) => {
+            let decoded = BASE64.decode(encoded.trim().as_bytes()).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+            match decoded.as_deref().and_then(|value| value.split_once(':')) {
+                Some((client_id, client_secret)) => Ok(ClientCredentials { client_id: client_id.to_string(), client_secret: Some(client_secret.to_string()) }),
+                None => Err(OAuthError::new("invalid_client", "malformed basic authorization header")),
+            }
+        },
+        (None, Some(client_id)) => Ok(ClientCredentials { client_id: client_id.clone(), client_secret: request.client_secret.clone() }),
+        (Some(_), Some(_)) => Err(OAuthError::new("invalid_request", "use only one client authentication method")),
+        (None, None) => Err(OAuthError::new("invalid_client", "client authentication is required")),
+    }
+}
+
+fn session_cookie(req: &HttpRequest) -> Option<String> {
+    req.cookie(SESSION_COOKIE).map(|cookie| cookie.value().to_string())
+}
+
+#[get("/authorize")]
+async fn authorize_handler(
+    req: HttpRequest,
+    query: Query<AuthorizeQuery>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oidc_authorize_handler";
+    let log_id = format!("{} Oidc.Authorize.{}",chrono::Utc::now(),query.client_id);
+    let session = session_cookie(&req);
+
+    let redirect = OidcServices::authorize(&log_id, session.as_deref(), query.into_inner(), req.query_string(), &app_state.oidc, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oidc_authorize.failed", error))?;
+
+    Ok(HttpResponse::Found().insert_header((LOCATION, redirect)).finish())
+}
+
+// The login page trades the access token it just got for the session cookie /authorize reads.
+#[post("/session", wrap = "AccessTokenMW")]
+async fn create_session_handler(
+    req: HttpRequest,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oidc_create_session_handler";
+    let claims = access_claims(&req)?;
+    let log_id = format!("{} Oidc.Session.{}",chrono::Utc::now(),claims.sub);
+
+    let session = OidcServices::create_session(&log_id, &claims, &app_state.redis, &app_state.jwt)
+        .map_err(|error| log_failure(handler_name, &log_id, "oidc_create_session.failed", error))?;
+
+    let cookie = Cookie::build(SESSION_COOKIE, session)
+        .path("/api/oauth")
+        .http_only(true)
+        .secure(app_state.oidc.base_url.starts_with("https://"))
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::seconds(app_state.oidc.session_lifetime))
+        .finish();
+
+    Ok(HttpResponse::Ok().cookie(cookie).json(json!({
+        "status":"success",
+        "message":"session created"
+    })))
+}
+
+#[get("/consent/{consent_id}")]
+async fn consent_handler(
+    req: HttpRequest,
+    path: Path<String>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oidc_consent_handler";
+    let log_id = format!("{} Oidc.Consent",chrono::Utc::now());
+    let session = session_cookie(&req);
+
+    let consent = OidcServices::consent(&log_id, session.as_deref(), &path.into_inner(), &app_state.oidc, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oidc_consent.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get consent success",
+        "data":consent
+    })))
+}
+
+// A plain form post from the consent page; the answer is a redirect back to the client.
+#[post("/consent")]
+async fn decide_consent_handler(
+    req: HttpRequest,
+    decision_form: Form<ConsentDecision>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oidc_decide_consent_handler";
+    let log_id = format!("{} Oidc.DecideConsent",chrono::Utc::now());
+    let session = session_cookie(&req);
+
+    let redirect = OidcServices::decide_consent(&log_id, session.as_deref(), decision_form.into_inner(), &app_state.oidc, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oidc_decide_consent.failed", error))?;
+
+    Ok(HttpResponse::SeeOther().insert_header((LOCATION, redirect)).finish())
+}
+
+#[post("/token")]
+async fn token_handler(
+    req: HttpRequest,
+    token_form: Form<TokenRequest>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, OAuthError>{
+    let handler_name = "oidc_token_handler";
+    let request = token_form.into_inner();
+    let credentials = client_credentials(&req, &request)?;
+    let log_id = format!("{} Oidc.Token.{}",chrono::Utc::now(),credentials.client_id);
+
+    let payload = OidcServices::token(&log_id, credentials, request, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .inspect_err(|error| Logger::warning_logger(handler_name, &log_id, "oidc_token.failed", &error.to_string()))?;
+
+    Logger::info_logger(handler_name, &log_id, "oidc_token.success");
+    Ok(HttpResponse::Ok()
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .insert_header((PRAGMA, "no-cache"))
+        .json(payload))
+}
+
+// Takes the client access token issued by /token, which AccessTokenMW deliberately rejects.
+#[get("/userinfo")]
+async fn userinfo_handler(
+    req: HttpRequest,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "oidc_userinfo_handler";
+    let log_id = format!("{} Oidc.Userinfo",chrono::Utc::now());
+    let access_token = bearer_token(req.headers()).ok_or(AuthError::Unauthorized(String::from("token not found")))?;
+
+    let userinfo = OidcServices::userinfo(&log_id, &access_token, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "oidc_userinfo.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(userinfo))
+}
+
+#[get("/.well-known/openid-configuration")]
+async fn discovery_handler(
+    app_state: Data<AppState>
+)-> HttpResponse{
+    HttpResponse::Ok().json(OidcServices::discovery(&app_state.oidc, &app_state.jwt))
+}
+
+#[post("/oauth/clients")]
+pub async fn create_client_handler(
+    user: AuthUser,
+    client_body: Json<CreateClientData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_create_client_handler";
+    let client_data = json_validate(client_body)?;
+    let log_id = client_data.request_id.to_string();
+    let actor = user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_create_client.require_role", error))?;
+
+    let client = OidcServices::create_client(&log_id, actor, client_data, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "admin_create_client.failed", error))?;
+
+    Ok(HttpResponse::Created().json(json!({
+        "status":"success",
+        "message":"client created, the secret is only shown once",
+        "data":client
+    })))
+}
+
+#[get("/oauth/clients")]
+pub async fn list_clients_handler(
+    user: AuthUser,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_list_clients_handler";
+    let log_id = format!("{} Admin.Clients",chrono::Utc::now());
+    user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_list_clients.require_role", error))?;
+
+    let clients = OidcServices::list_clients(&log_id, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "admin_list_clients.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get clients success",
+        "data":clients
+    })))
+}
+
+#[delete("/oauth/clients/{client_id}")]
+pub async fn delete_client_handler(
+    user: AuthUser,
+    path: Path<String>,
+    action_body: Json<AdminActionData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "admin_delete_client_handler";
+    let action_data = json_validate(action_body)?;
+    let log_id = action_data.request_id.to_string();
+    let actor = user.require_role(ADMIN_ROLE).map_err(|error| log_failure(handler_name, &log_id, "admin_delete_client.require_role", error))?;
+
+    OidcServices::delete_client(&log_id, actor, &path.into_inner(), action_data, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "admin_delete_client.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"client deleted"
+    })))
+}
+
+pub fn discovery_config(config:&mut ServiceConfig){
+    config.service(discovery_handler);
+}
+
+pub fn oidc_config(config:&mut ServiceConfig){
+    config.service(
+        scope("/oauth")
+        .service(authorize_handler)
+        .service(create_session_handler)
+        .service(consent_handler)
+        .service(decide_consent_handler)
+        .service(token_handler)
+        .service(userinfo_handler)
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{cookie::Cookie, http::{header::HeaderValue, StatusCode}, test::{self, TestRequest}, web::scope, App};
+    use argon2::{password_hash::{rand_core::OsRng, SaltString}, Argon2, PasswordHasher};
+    use jwt_libs::{testing::keyring_with, types::JwtClaimsConfig};
+    use redis_libs::testing::test_redis_pool;
+    use reqwest::Url;
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use crate::{config_type::{LoginLimit, Oidc}, modules::{mfa::totp::SecretCipher, oauth::provider::code_challenge, user::{handler::auth_config, query::hash_token}}};
+
+    use super::*;
+
+    fn token_request(client_id: Option<&str>) -> TokenRequest {
+        TokenRequest {
+            grant_type: String::from("client_credentials"),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: None,
+            scope: None,
+            client_id: client_id.map(String::from),
+            client_secret: Some(String::from("form-secret")),
+        }
+    }
+
+    #[test]
+    fn reads_client_credentials() {
+        let basic = HeaderValue::from_str(&format!("Basic {}", BASE64.encode(b"post_gateway:s3cret"))).unwrap();
+        let req = TestRequest::default().insert_header((AUTHORIZATION, basic)).to_http_request();
+        let credentials = client_credentials(&req, &token_request(None)).unwrap();
+        assert_eq!(credentials, ClientCredentials { client_id: String::from("post_gateway"), client_secret: Some(String::from("s3cret")) });
+        assert_eq!(client_credentials(&req, &token_request(Some("post_gateway"))).unwrap_err().error, "invalid_request");
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(client_credentials(&req, &token_request(Some("tool"))).unwrap().client_secret.as_deref(), Some("form-secret"));
+        assert_eq!(client_credentials(&req, &token_request(None)).unwrap_err().error, "invalid_client");
+    }
+
+    fn app_state(db: PgPool) -> Data<AppState> {
+        let jwt = keyring_with(JwtClaimsConfig { issuer: String::from("http://localhost:8080"), audience: String::from("auth_services"), access_audience: Vec::new() });
+
+        Data::new(AppState {
+            db,
+            redis: test_redis_pool(),
+            rabbit: rabbitmq_libs::rabbit_connect(String::from("amqp://localhost:5672"), 1).unwrap(),
+            jwt,
+            mail: Default::default(),
+            login_limit: LoginLimit { window: 60, account_attempts: 10, ip_attempts: 10, max_failures: 5, failure_window: 60, lockout: 60 },
+            mfa: Default::default(),
+            secret_cipher: SecretCipher::from_hex(&"00".repeat(32)).unwrap(),
+            oauth: Default::default(),
+            identity_providers: Default::default(),
+            oidc: Oidc {
+                base_url: String::from("http://localhost:8080"),
+                code_lifetime: 60,
+                login_url: String::from("http://localhost:8080/login"),
+                consent_url: String::from("http://localhost:8080/consent"),
+                session_lifetime: 3600,
+                consent_lifetime: 600,
+            },
+        })
+    }
+
+    async fn seed(db: &PgPool) {
+        let password = Argon2::default().hash_password(b"Passw0rd!", &SaltString::generate(&mut OsRng)).unwrap().to_string();
+        sqlx::query(r#"INSERT INTO "user" (email, username, phonenumber, password, email_verified) VALUES ('tester@mail.com', 'tester', '0800000000', $1, true)"#)
+            .bind(password).execute(db).await.unwrap();
+        sqlx::query(r#"INSERT INTO "oauth_client" (client_id, client_secret_hash, name, redirect_uris, grant_types, scopes) VALUES ('tool', $1, 'internal tool', $2, $3, $4)"#)
+            .bind(hash_token("tool-secret"))
+            .bind(vec!["https://tool.local/callback"])
+            .bind(vec!["authorization_code"])
+            .bind(vec!["openid", "email", "profile"])
+            .execute(db).await.unwrap();
+    }
+
+    fn location(res: &actix_web::dev::ServiceResponse) -> Url {
+        Url::parse(res.headers().get(LOCATION).unwrap().to_str().unwrap()).unwrap()
+    }
+
+    fn query_param(url: &Url, name: &str) -> String {
+        url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned()).unwrap()
+    }
+
+    // The pool is never connected, so only routes that stay off the database can be called.
+    fn app_state_without_db() -> Data<AppState> {
+        app_state(PgPool::connect_lazy("postgres://localhost/unused").unwrap())
+    }
+
+    #[actix_web::test]
+    async fn session_requires_an_access_token_and_sets_the_cookie() {
+        let app_state = app_state_without_db();
+        let access_token = jwt_libs::generate_access_token(jwt_libs::testing::access_token().build(), &app_state.jwt).unwrap();
+        let app = test::init_service(App::new().app_data(app_state).service(scope("/api").configure(oidc_config))).await;
+
+        let error = test::try_call_service(&app, TestRequest::post().uri("/api/oauth/session").to_request()).await.err().unwrap();
+        assert_eq!(error.error_response().status(), StatusCode::UNAUTHORIZED);
+
+        let res = test::call_service(&app, TestRequest::post().uri("/api/oauth/session").insert_header((AUTHORIZATION, format!("Bearer {}", access_token))).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let session = res.response().cookies().find(|cookie| cookie.name() == SESSION_COOKIE).unwrap().into_owned();
+        assert_eq!((session.path(), session.http_only(), session.same_site()), (Some("/api/oauth"), Some(true), Some(SameSite::Lax)));
+        assert_eq!(session.max_age(), Some(CookieDuration::seconds(3600)));
+        assert!(!session.value().is_empty());
+    }
+
+    // A browser with nothing but its cookie jar: login page, session cookie, consent, then the client's back channel.
+    // Needs a Postgres DATABASE_URL: cargo test -p auth_services -- --ignored
+    #[sqlx::test(migrations = "../../migrations")]
+    #[ignore = "needs a Postgres DATABASE_URL"]
+    async fn authorize_goes_through_login_and_consent(db: PgPool) {
+        seed(&db).await;
+        let app = test::init_service(App::new().app_data(app_state(db)).service(scope("/api").configure(auth_config).configure(oidc_config))).await;
+
+        let challenge = code_challenge("verifier-0123456789");
+        let authorize = format!("/api/oauth/authorize?response_type=code&client_id=tool&redirect_uri=https%3A%2F%2Ftool.local%2Fcallback&scope=openid%20email&state=s1&nonce=n1&code_challenge={}&code_challenge_method=S256", challenge);
+
+        let res = test::call_service(&app, TestRequest::get().uri(&authorize).to_request()).await;
+        assert_eq!(res.status(), StatusCode::FOUND);
+        let login = location(&res);
+        assert_eq!(login.path(), "/login");
+        let return_to = Url::parse(&query_param(&login, "return_to")).unwrap();
+        assert_eq!(format!("{}?{}", return_to.path(), return_to.query().unwrap()), authorize);
+
+        let silent = test::call_service(&app, TestRequest::get().uri(&format!("{}&prompt=none", authorize)).to_request()).await;
+        assert_eq!(query_param(&location(&silent), "error"), "login_required");
+
+        // What the login page does with the user's credentials.
+        let login_body = serde_json::json!({ "request_id": "it-login", "username": "tester", "password": "Passw0rd!" });
+        let res = test::call_service(&app, TestRequest::post().uri("/api/auth/login").set_json(login_body).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(res).await;
+        let access_token = body["data"]["access_token"].as_str().unwrap().to_string();
+
+        let res = test::call_service(&app, TestRequest::post().uri("/api/oauth/session").insert_header((AUTHORIZATION, format!("Bearer {}", access_token))).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let session = res.response().cookies().find(|cookie| cookie.name() == SESSION_COOKIE).unwrap().into_owned();
+        assert!(session.http_only().unwrap_or_default());
+        let session = Cookie::new(SESSION_COOKIE, session.value().to_string());
+
+        let res = test::call_service(&app, TestRequest::get().uri(&authorize).cookie(session.clone()).to_request()).await;
+        let consent = location(&res);
+        assert_eq!(consent.path(), "/consent");
+        let consent_id = query_param(&consent, "consent_id");
+
+        let res = test::call_service(&app, TestRequest::get().uri(&format!("/api/oauth/consent/{}", consent_id)).to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = test::call_service(&app, TestRequest::get().uri(&format!("/api/oauth/consent/{}", consent_id)).cookie(session.clone()).to_request()).await;
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["data"]["client_name"], "internal tool");
+        assert_eq!(body["data"]["scopes"], serde_json::json!(["openid", "email"]));
+
+        let res = test::call_service(&app, TestRequest::post().uri("/api/oauth/consent").cookie(session.clone()).set_form([("consent_id", consent_id.as_str()), ("approve", "true")]).to_request()).await;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let callback = location(&res);
+        assert_eq!((callback.host_str(), query_param(&callback, "state").as_str()), (Some("tool.local"), "s1"));
+        let code = query_param(&callback, "code");
+
+        let token_form = [("grant_type", "authorization_code"), ("code", code.as_str()), ("redirect_uri", "https://tool.local/callback"), ("code_verifier", "verifier-0123456789"), ("client_id", "tool"), ("client_secret", "tool-secret")];
+        let res = test::call_service(&app, TestRequest::post().uri("/api/oauth/token").set_form(token_form).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let tokens: Value = test::read_body_json(res).await;
+        assert_eq!(tokens["scope"], "openid email");
+        assert!(tokens["id_token"].is_string());
+
+        let client_token = format!("Bearer {}", tokens["access_token"].as_str().unwrap());
+        let res = test::call_service(&app, TestRequest::get().uri("/api/oauth/userinfo").insert_header((AUTHORIZATION, client_token)).to_request()).await;
+        let userinfo: Value = test::read_body_json(res).await;
+        assert_eq!(userinfo["email"], "tester@mail.com");
+        assert!(userinfo.get("preferred_username").is_none());
+
+        // The approved scopes are remembered, so the next authorize goes straight back to the client.
+        let res = test::call_service(&app, TestRequest::get().uri(&authorize).cookie(session).to_request()).await;
+        assert!(query_param(&location(&res), "code").len() > 20);
+    }
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use serde::{Serialize,Deserialize};
+use uuid::Uuid;
+use validator::Validate;
+
+pub const GRANT_AUTHORIZATION_CODE: &str = "authorization_code";
+pub const GRANT_REFRESH_TOKEN: &str = "refresh_token";
+pub const GRANT_CLIENT_CREDENTIALS: &str = "client_credentials";
+pub const SUPPORTED_GRANTS: [&str; 3] = [GRANT_AUTHORIZATION_CODE, GRANT_REFRESH_TOKEN, GRANT_CLIENT_CREDENTIALS];
+
+pub const SCOPE_OPENID: &str = "openid";
+pub const SCOPE_PROFILE: &str = "profile";
+pub const SCOPE_EMAIL: &str = "email";
+pub const USER_SCOPES: [&str; 3] = [SCOPE_OPENID, SCOPE_PROFILE, SCOPE_EMAIL];
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct OAuthClientRow{
+    pub id: Uuid,
+    pub client_id: String,
+    pub client_secret_hash: Option<String>,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>
+}
+
+impl OAuthClientRow {
+    pub fn allows_grant(&self, grant_type: &str) -> bool {
+        self.grant_types.iter().any(|grant| grant == grant_type)
+    }
+
+    pub fn allows_scopes(&self, scopes: &[String]) -> bool {
+        scopes.iter().all(|scope| self.scopes.contains(scope))
+    }
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct ClientPayload{
+    pub client_id: String,
+    pub name: String,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<String>,
+    pub scopes: Vec<String>,
+    pub public: bool,
+    pub created_at: DateTime<Utc>
+}
+
+impl From<OAuthClientRow> for ClientPayload {
+    fn from(client: OAuthClientRow) -> Self {
+        ClientPayload {
+            public: client.client_secret_hash.is_none(),
+            client_id: client.client_id,
+            name: client.name,
+            redirect_uris: client.redirect_uris,
+            grant_types: client.grant_types,
+            scopes: client.scopes,
+            created_at: client.created_at,
+        }
+    }
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct CreatedClientPayload{
+    pub client: ClientPayload,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct CreateClientData{
+    pub request_id: String,
+    #[validate(length(min=3, max=64, message="must be between 3 and 64 characters"))]
+    pub client_id: String,
+    #[validate(length(min=1, max=100, message="must be between 1 and 100 characters"))]
+    pub name: String,
+    #[serde(default)]
+    #[validate(length(max=10, message="too many redirect uris"))]
+    pub redirect_uris: Vec<String>,
+    #[validate(length(min=1, message="at least one grant type is required"))]
+    pub grant_types: Vec<String>,
+    #[validate(length(max=20, message="too many scopes"))]
+    pub scopes: Vec<String>,
+    #[serde(default)]
+    pub public: bool
+}
+
+pub const PROMPT_NONE: &str = "none";
+pub const PROMPT_CONSENT: &str = "consent";
+
+#[derive(Debug,Deserialize,Serialize,Clone,PartialEq,Eq)]
+pub struct AuthorizeQuery{
+    pub response_type: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub prompt: Option<String>
+}
+
+// Browser session behind the oidc_session cookie; iat is the login it came from, so subject revocation ends it too.
+#[derive(Debug,Deserialize,Serialize,PartialEq,Eq)]
+pub struct OidcSession{
+    pub user_id: Uuid,
+    pub iat: i64
+}
+
+#[derive(Debug,Deserialize,Serialize,PartialEq,Eq)]
+pub struct PendingConsent{
+    pub user_id: Uuid,
+    pub scopes: Vec<String>,
+    pub request: AuthorizeQuery
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct ConsentPayload{
+    pub client_id: String,
+    pub client_name: String,
+    pub scopes: Vec<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct ConsentDecision{
+    pub consent_id: String,
+    pub approve: bool
+}
+
+#[derive(Debug,Deserialize,Serialize,PartialEq,Eq)]
+pub struct AuthorizationCode{
+    pub client_id: String,
+    pub user_id: Uuid,
+    pub redirect_uri: String,
+    pub scopes: Vec<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: String
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct TokenRequest{
+    pub grant_type: String,
+    pub code: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<String>,
+    pub refresh_token: Option<String>,
+    pub scope: Option<String>,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ClientCredentials{
+    pub client_id: String,
+    pub client_secret: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct TokenPayload{
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    pub scope: String
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct ClientRefreshTokenRow{
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub used_at: Option<DateTime<Utc>>,
+    pub scope: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct UserInfoRow{
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool
+}
+
+#[derive(Debug,Deserialize,Serialize,PartialEq)]
+pub struct UserInfoPayload{
+    pub sub: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>
+}
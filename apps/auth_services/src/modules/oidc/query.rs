@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{query, query_as, PgPool};
+use uuid::Uuid;
+
+use crate::{error::AuthError, modules::{admin::query::insert_audit, user::query::hash_token}};
+
+use super::model::{ClientRefreshTokenRow, CreateClientData, OAuthClientRow, UserInfoRow};
+
+pub struct OidcQuery {}
+
+impl OidcQuery {
+    pub async fn find_client(
+        client_id: &str,
+        db_pool: &PgPool
+    ) -> Result<Option<OAuthClientRow>, AuthError> {
+        match query_as!(
+            OAuthClientRow,
+            r#"
+            SELECT id, client_id, client_secret_hash, name, redirect_uris, grant_types, scopes, created_at
+            FROM "oauth_client" WHERE client_id = $1
+            "#,
+            client_id
+        ).fetch_optional(db_pool).await{
+            Ok(client)=>Ok(client),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn list_clients(
+        db_pool: &PgPool
+    ) -> Result<Vec<OAuthClientRow>, AuthError> {
+        match query_as!(
+            OAuthClientRow,
+            r#"
+            SELECT id, client_id, client_secret_hash, name, redirect_uris, grant_types, scopes, created_at
+            FROM "oauth_client" ORDER BY created_at DESC, client_id
+            "#
+        ).fetch_all(db_pool).await{
+            Ok(clients)=>Ok(clients),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn create_client(
+        actor_id: Uuid,
+        data: &CreateClientData,
+        client_secret_hash: Option<String>,
+        details: Value,
+        db_pool: &PgPool
+    ) -> Result<OAuthClientRow, AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let client = query_as!(
+            OAuthClientRow,
+            r#"
+            INSERT INTO "oauth_client"
+            (client_id, client_secret_hash, name, redirect_uris, grant_types, scopes) VALUES
+            ($1, $2, $3, $4, $5, $6)
+            RETURNING id, client_id, client_secret_hash, name, redirect_uris, grant_types, scopes, created_at
+            "#,
+            data.client_id,
+            client_secret_hash,
+            data.name,
+            &data.redirect_uris,
+            &data.grant_types,
+            &data.scopes
+        ).fetch_one(&mut *tx).await?;
+
+        insert_audit(&mut tx, actor_id, None, "oauth_client.create", details).await?;
+
+        tx.commit().await?;
+        Ok(client)
+    }
+
+    pub async fn delete_client(
+        actor_id: Uuid,
+        client_id: &str,
+        details: Value,
+        db_pool: &PgPool
+    ) -> Result<bool, AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let result = query!(
+            r#"
+            DELETE FROM "oauth_client" WHERE client_id = $1
+            "#,
+            client_id
+        ).execute(&mut *tx).await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        insert_audit(&mut tx, actor_id, None, "oauth_client.delete", details).await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_client_refresh_token(
+        token: &str,
+        user_id: Uuid,
+        family_id: Uuid,
+        expires_at: DateTime<Utc>,
+        client_id: &str,
+        scope: &str,
+        db_pool: &PgPool
+    ) -> Result<(), AuthError> {
+        match query!(
+            r#"
+            INSERT INTO "refresh_token"
+            (userid, token_hash, family_id, expires_at, client_id, scope) VALUES
+            ($1, $2, $3, $4, $5, $6)
+            "#,
+            user_id,
+            hash_token(token),
+            family_id,
+            expires_at,
+            client_id,
+            scope
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_client_refresh_token(
+        token: &str,
+        user_id: Uuid,
+        client_id: &str,
+        db_pool: &PgPool
+    ) -> Result<ClientRefreshTokenRow, AuthError> {
+        match query_as!(
+            ClientRefreshTokenRow,
+            r#"
+            SELECT id, family_id, used_at, scope FROM "refresh_token"
+            WHERE userid = $1 AND token_hash = $2 AND client_id = $3 AND expires_at > now()
+            "#,
+            user_id,
+            hash_token(token),
+            client_id
+        ).fetch_optional(db_pool).await{
+            Ok(Some(row))=>Ok(row),
+            Ok(None)=>Err(AuthError::Unauthorized(String::from("invalid token: refresh token not found"))),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_userinfo(
+        user_id: Uuid,
+        db_pool: &PgPool
+    ) -> Result<Option<UserInfoRow>, AuthError> {
+        match query_as!(
+            UserInfoRow,
+            r#"
+            SELECT id, username, email, email_verified
+            FROM "user" WHERE id = $1
+            "#,
+            user_id
+        ).fetch_optional(db_pool).await{
+            Ok(user)=>Ok(user),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_consent(
+        user_id: Uuid,
+        client_id: &str,
+        db_pool: &PgPool
+    ) -> Result<Vec<String>, AuthError> {
+        match query!(
+            r#"
+            SELECT scopes FROM "oauth_consent" WHERE user_id = $1 AND client_id = $2
+            "#,
+            user_id,
+            client_id
+        ).fetch_optional(db_pool).await{
+            Ok(consent)=>Ok(consent.map(|consent| consent.scopes).unwrap_or_default()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    // Approving more scopes later extends the consent instead of replacing it.
+    pub async fn save_consent(
+        user_id: Uuid,
+        client_id: &str,
+        scopes: &[String],
+        db_pool: &PgPool
+    ) -> Result<(), AuthError> {
+        match query!(
+            r#"
+            INSERT INTO "oauth_consent" (user_id, client_id, scopes) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, client_id) DO UPDATE
+            SET scopes = ARRAY(SELECT DISTINCT unnest("oauth_consent".scopes || EXCLUDED.scopes)), updated_at = now()
+            "#,
+            user_id,
+            client_id,
+            scopes
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+}
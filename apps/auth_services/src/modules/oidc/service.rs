@@ -0,0 +1,865 @@
+use chrono::Utc;
+use jwt_libs::{decode_client_access_token, decode_refresh_token, error::JwtLibError, generate_client_access_token, generate_id_token, generate_refresh_token, generate_service_token, keys::JwtKeyring, types::{AccessToken, ClientAccessToken, IdToken, RefreshToken, ServiceToken, TokenClaims}};
+use logger_libs::Logger;
+use pgsql_libs::DbPool;
+use redis_libs::{fetch_value, is_subject_disabled, is_token_revoked, revoke_subject_tokens, store_one_time, take_one_time, RedisPool};
+use reqwest::Url;
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::{config_type::Oidc, error::{AuthError, OAuthError}, modules::{admin::model::AdminActionData, oauth::{provider::{code_challenge, random_token}, query::OAuthQuery}, user::{query::{hash_token, UserQuery}, service::token_error}}};
+
+use super::{model::{AuthorizationCode, AuthorizeQuery, ClientCredentials, ClientPayload, ConsentDecision, ConsentPayload, OidcSession, PendingConsent, PROMPT_CONSENT, PROMPT_NONE, CreateClientData, CreatedClientPayload, OAuthClientRow, TokenPayload, TokenRequest, UserInfoPayload, UserInfoRow, GRANT_AUTHORIZATION_CODE, GRANT_CLIENT_CREDENTIALS, GRANT_REFRESH_TOKEN, SCOPE_EMAIL, SCOPE_OPENID, SCOPE_PROFILE, SUPPORTED_GRANTS, USER_SCOPES}, query::OidcQuery};
+
+pub struct OidcServices{}
+
+const OIDC_CODE_PREFIX: &str = "oidc_code";
+const OIDC_SESSION_PREFIX: &str = "oidc_session";
+const OIDC_CONSENT_PREFIX: &str = "oidc_consent";
+pub const SESSION_COOKIE: &str = "oidc_session";
+
+struct UserGrant {
+    user_id: Uuid,
+    // scopes of this token response; a refresh grant may narrow them but the stored refresh token keeps `granted`
+    scopes: Vec<String>,
+    granted: Vec<String>,
+    nonce: Option<String>,
+    family_id: Uuid,
+}
+
+fn code_key(code: &str) -> String {
+    format!("{}:{}", OIDC_CODE_PREFIX, hash_token(code))
+}
+
+fn session_key(session: &str) -> String {
+    format!("{}:{}", OIDC_SESSION_PREFIX, hash_token(session))
+}
+
+fn consent_key(consent_id: &str) -> String {
+    format!("{}:{}", OIDC_CONSENT_PREFIX, hash_token(consent_id))
+}
+
+pub fn parse_scope(scope: Option<&str>) -> Vec<String> {
+    let mut scopes: Vec<String> = Vec::new();
+    for scope in scope.unwrap_or_default().split_whitespace() {
+        if !scopes.iter().any(|known| known == scope) {
+            scopes.push(scope.to_string());
+        }
+    }
+    scopes
+}
+
+fn redirect_uri_with(redirect_uri: &str, params: &[(&str, &str)], state: Option<&str>) -> Result<String, AuthError> {
+    let mut url = Url::parse(redirect_uri).map_err(|error| AuthError::validation(format!("invalid redirect_uri: {}", error)))?;
+    {
+        let mut query = url.query_pairs_mut();
+        for (key, value) in params {
+            query.append_pair(key, value);
+        }
+        if let Some(state) = state {
+            query.append_pair("state", state);
+        }
+    }
+    Ok(url.to_string())
+}
+
+fn constant_time_eq(left: &str, right: &str) -> bool {
+    left.as_bytes().ct_eq(right.as_bytes()).into()
+}
+
+fn client_secret_matches(client: &OAuthClientRow, client_secret: Option<&str>) -> bool {
+    match (&client.client_secret_hash, client_secret) {
+        (Some(secret_hash), Some(client_secret)) => constant_time_eq(secret_hash, &hash_token(client_secret)),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+// OIDC clients compare the issuer with the discovery location, so it has to be that url and use https outside local development.
+pub fn validate_issuer(base_url: &str, issuer: &str) -> Result<(), String> {
+    if issuer.trim_end_matches('/') != base_url.trim_end_matches('/') {
+        return Err(format!("jwt issuer {} must match oidc base_url {}", issuer, base_url));
+    }
+
+    let url = Url::parse(issuer).map_err(|error| format!("jwt issuer {} is not a url: {}", issuer, error))?;
+    let local = matches!(url.host_str(), Some("localhost") | Some("127.0.0.1") | Some("[::1]"));
+    match url.scheme() {
+        "https" => Ok(()),
+        "http" if local => Ok(()),
+        _ => Err(format!("jwt issuer {} must use https", issuer)),
+    }
+}
+
+fn scoped_userinfo(user: UserInfoRow, token: &ClientAccessToken) -> UserInfoPayload {
+    UserInfoPayload {
+        sub: user.id,
+        preferred_username: token.has_scope(SCOPE_PROFILE).then_some(user.username),
+        email: token.has_scope(SCOPE_EMAIL).then_some(user.email),
+        email_verified: token.has_scope(SCOPE_EMAIL).then_some(user.email_verified),
+    }
+}
+
+fn server_error(step: &str, error: impl std::fmt::Display) -> OAuthError {
+    OAuthError::from(AuthError::Internal(format!("error {}: {}", step, error)))
+}
+
+impl OidcServices {
+    // Browsers cannot send a bearer token here, so the user comes from the oidc_session cookie; without one
+    // they go through the login page and come back, and scopes they have not approved go through the consent page.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn authorize(
+        log_id: &str,
+        session: Option<&str>,
+        request: AuthorizeQuery,
+        query_string: &str,
+        oidc: &Oidc,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<String, AuthError> {
+        let handler_name = "oidc_authorize_services";
+        let client = match OidcQuery::find_client(&request.client_id, db_pool).await? {
+            Some(client) => client,
+            None => return Err(AuthError::validation("unknown client_id")),
+        };
+        if !client.redirect_uris.contains(&request.redirect_uri) {
+            return Err(AuthError::validation("redirect_uri is not registered for this client"));
+        }
+
+        // The redirect_uri is trusted from here on, so the remaining errors go back to the client through it.
+        let state = request.state.as_deref();
+        let scopes = parse_scope(request.scope.as_deref());
+        let rejection = if request.response_type != "code" {
+            Some(("unsupported_response_type", "only the code response type is supported"))
+        } else if !client.allows_grant(GRANT_AUTHORIZATION_CODE) {
+            Some(("unauthorized_client", "client may not use the authorization code flow"))
+        } else if !scopes.iter().any(|scope| scope == SCOPE_OPENID) || !client.allows_scopes(&scopes) {
+            Some(("invalid_scope", "scope must include openid and only scopes registered for the client"))
+        } else if request.code_challenge.is_none() || request.code_challenge_method.as_deref() != Some("S256") {
+            Some(("invalid_request", "PKCE with code_challenge_method S256 is required"))
+        } else {
+            None
+        };
+        if let Some((error, description)) = rejection {
+            Logger::warning_logger(handler_name, log_id, "oidc_authorize_services.validate_request", description);
+            return redirect_uri_with(&request.redirect_uri, &[("error", error), ("error_description", description)], state);
+        }
+
+        let user_id = Self::session_user(log_id, session, oidc.session_lifetime, redis_pool, jwt_keyring)?;
+        let consented = match user_id {
+            Some(user_id) => OidcQuery::find_consent(user_id, &client.client_id, db_pool).await?,
+            None => Vec::new(),
+        };
+        Self::authorize_user(log_id, user_id, &consented, request, scopes, query_string, oidc, redis_pool)
+    }
+
+    // The rest of a valid authorize request: login page without a user, consent page for scopes not approved yet.
+    #[allow(clippy::too_many_arguments)]
+    fn authorize_user(
+        log_id: &str,
+        user_id: Option<Uuid>,
+        consented: &[String],
+        request: AuthorizeQuery,
+        scopes: Vec<String>,
+        query_string: &str,
+        oidc: &Oidc,
+        redis_pool: &RedisPool
+    ) -> Result<String, AuthError> {
+        let handler_name = "oidc_authorize_user_services";
+        let state = request.state.as_deref();
+        let prompt = request.prompt.as_deref();
+
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None if prompt == Some(PROMPT_NONE) => {
+                return redirect_uri_with(&request.redirect_uri, &[("error", "login_required"), ("error_description", "user is not signed in")], state);
+            },
+            None => {
+                Logger::info_logger(handler_name, log_id, "oidc_authorize_user_services.login_redirect");
+                let return_to = format!("{}/api/oauth/authorize?{}", oidc.base_url.trim_end_matches('/'), query_string);
+                return redirect_uri_with(&oidc.login_url, &[("return_to", &return_to)], None);
+            },
+        };
+
+        if prompt != Some(PROMPT_CONSENT) && scopes.iter().all(|scope| consented.contains(scope)) {
+            return Self::issue_code(log_id, user_id, request, scopes, oidc.code_lifetime, redis_pool);
+        }
+        if prompt == Some(PROMPT_NONE) {
+            return redirect_uri_with(&request.redirect_uri, &[("error", "consent_required"), ("error_description", "the requested scopes need the user's consent")], state);
+        }
+
+        let consent_id = random_token();
+        let pending = PendingConsent { user_id, scopes, request };
+        let value = serde_json::to_string(&pending).map_err(|error| AuthError::Internal(format!("error serialize consent: {}", error)))?;
+        store_one_time(redis_pool, &consent_key(&consent_id), &value, oidc.consent_lifetime).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_authorize_user_services.store_consent", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_authorize_user_services.consent_redirect");
+        redirect_uri_with(&oidc.consent_url, &[("consent_id", &consent_id)], None)
+    }
+
+    fn issue_code(
+        log_id: &str,
+        user_id: Uuid,
+        request: AuthorizeQuery,
+        scopes: Vec<String>,
+        code_lifetime: i64,
+        redis_pool: &RedisPool
+    ) -> Result<String, AuthError> {
+        let handler_name = "oidc_issue_code_services";
+        let code = random_token();
+        let authorization_code = AuthorizationCode {
+            client_id: request.client_id,
+            user_id,
+            redirect_uri: request.redirect_uri.clone(),
+            scopes,
+            nonce: request.nonce,
+            code_challenge: request.code_challenge.unwrap_or_default(),
+        };
+        let value = serde_json::to_string(&authorization_code).map_err(|error| AuthError::Internal(format!("error serialize authorization code: {}", error)))?;
+
+        store_one_time(redis_pool, &code_key(&code), &value, code_lifetime).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_issue_code_services.store_code", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_issue_code_services.store_code");
+        redirect_uri_with(&request.redirect_uri, &[("code", &code)], request.state.as_deref())
+    }
+
+    // Called by the login page with the access token it just obtained, so every login factor has been passed.
+    pub fn create_session(
+        log_id: &str,
+        claims: &TokenClaims<AccessToken>,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<String, AuthError> {
+        let handler_name = "oidc_create_session_services";
+        let session = random_token();
+        let value = serde_json::to_string(&OidcSession { user_id: claims.token.id, iat: claims.iat })
+            .map_err(|error| AuthError::Internal(format!("error serialize session: {}", error)))?;
+
+        store_one_time(redis_pool, &session_key(&session), &value, jwt_keyring.lifetimes().access_token).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_create_session_services.store_session", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_create_session_services.store_session");
+        Ok(session)
+    }
+
+    // Sessions idle out after one access token lifetime, which is as long as subject revocations stay visible,
+    // so a password change, logout_all or a disabled account always ends them.
+    fn session_user(
+        log_id: &str,
+        session: Option<&str>,
+        session_lifetime: i64,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<Option<Uuid>, AuthError> {
+        let handler_name = "oidc_session_user_services";
+        let Some(session) = session else {
+            return Ok(None);
+        };
+        let key = session_key(session);
+        let Some(value) = fetch_value(redis_pool, &key).map_err(AuthError::Upstream)? else {
+            return Ok(None);
+        };
+        let Ok(oidc_session) = serde_json::from_str::<OidcSession>(&value) else {
+            return Ok(None);
+        };
+
+        let subject = oidc_session.user_id.to_string();
+        let remaining = oidc_session.iat + session_lifetime - Utc::now().timestamp();
+        if remaining <= 0 || is_token_revoked(redis_pool, &key, &subject, oidc_session.iat).map_err(AuthError::Upstream)? {
+            Logger::warning_logger(handler_name, log_id, "oidc_session_user_services.session_ended", &subject);
+            take_one_time(redis_pool, &key).map_err(AuthError::Upstream)?;
+            return Ok(None);
+        }
+        if is_subject_disabled(redis_pool, &subject).map_err(AuthError::Upstream)? {
+            return Err(AuthError::Forbidden(String::from("account disabled")));
+        }
+
+        store_one_time(redis_pool, &key, &value, remaining.min(jwt_keyring.lifetimes().access_token)).map_err(AuthError::Upstream)?;
+        Ok(Some(oidc_session.user_id))
+    }
+
+    pub async fn consent(
+        log_id: &str,
+        session: Option<&str>,
+        consent_id: &str,
+        oidc: &Oidc,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<ConsentPayload, AuthError> {
+        let user_id = Self::session_user(log_id, session, oidc.session_lifetime, redis_pool, jwt_keyring)?
+            .ok_or(AuthError::Unauthorized(String::from("session not found")))?;
+        let stored = fetch_value(redis_pool, &consent_key(consent_id)).map_err(AuthError::Upstream)?;
+        let pending = Self::pending_consent(stored.as_deref(), user_id)?;
+
+        let client = OidcQuery::find_client(&pending.request.client_id, db_pool).await?
+            .ok_or(AuthError::NotFound(String::from("client not found")))?;
+        Ok(ConsentPayload { client_id: client.client_id, client_name: client.name, scopes: pending.scopes })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn decide_consent(
+        log_id: &str,
+        session: Option<&str>,
+        decision: ConsentDecision,
+        oidc: &Oidc,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<String, AuthError> {
+        let handler_name = "oidc_decide_consent_services";
+        let user_id = Self::session_user(log_id, session, oidc.session_lifetime, redis_pool, jwt_keyring)?
+            .ok_or(AuthError::Unauthorized(String::from("session not found")))?;
+        let stored = take_one_time(redis_pool, &consent_key(&decision.consent_id)).map_err(AuthError::Upstream)?;
+        let pending = Self::pending_consent(stored.as_deref(), user_id)?;
+
+        if !decision.approve {
+            Logger::info_logger(handler_name, log_id, "oidc_decide_consent_services.denied");
+            return redirect_uri_with(&pending.request.redirect_uri, &[("error", "access_denied"), ("error_description", "the user denied the request")], pending.request.state.as_deref());
+        }
+
+        OidcQuery::save_consent(user_id, &pending.request.client_id, &pending.scopes, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_decide_consent_services.save_consent", &error.to_string());
+        })?;
+        Self::issue_code(log_id, user_id, pending.request, pending.scopes, oidc.code_lifetime, redis_pool)
+    }
+
+    fn pending_consent(stored: Option<&str>, user_id: Uuid) -> Result<PendingConsent, AuthError> {
+        match stored.and_then(|value| serde_json::from_str::<PendingConsent>(value).ok()) {
+            Some(pending) if pending.user_id == user_id => Ok(pending),
+            Some(_) => Err(AuthError::Forbidden(String::from("consent belongs to another user"))),
+            None => Err(AuthError::NotFound(String::from("consent request not found or expired"))),
+        }
+    }
+
+    pub async fn token(
+        log_id: &str,
+        credentials: ClientCredentials,
+        request: TokenRequest,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<TokenPayload, OAuthError> {
+        let handler_name = "oidc_token_services";
+        let client = Self::authenticate_client(log_id, &credentials, db_pool).await?;
+
+        if !SUPPORTED_GRANTS.contains(&request.grant_type.as_str()) {
+            return Err(OAuthError::new("unsupported_grant_type", format!("unsupported grant_type: {}", request.grant_type)));
+        }
+        if !client.allows_grant(&request.grant_type) {
+            Logger::warning_logger(handler_name, log_id, "oidc_token_services.allows_grant", &request.grant_type);
+            return Err(OAuthError::new("unauthorized_client", format!("client may not use the {} grant", request.grant_type)));
+        }
+
+        match request.grant_type.as_str() {
+            GRANT_AUTHORIZATION_CODE => Self::exchange_code(log_id, &client, request, db_pool, redis_pool, jwt_keyring).await,
+            GRANT_REFRESH_TOKEN => Self::refresh(log_id, &client, request, db_pool, jwt_keyring).await,
+            _ => Self::client_credentials(log_id, &client, request, jwt_keyring),
+        }
+    }
+
+    async fn authenticate_client(
+        log_id: &str,
+        credentials: &ClientCredentials,
+        db_pool: &DbPool
+    ) -> Result<OAuthClientRow, OAuthError> {
+        let handler_name = "oidc_authenticate_client_services";
+        let client = match OidcQuery::find_client(&credentials.client_id, db_pool).await? {
+            Some(client) => client,
+            None => return Err(OAuthError::new("invalid_client", "client authentication failed")),
+        };
+
+        if !client_secret_matches(&client, credentials.client_secret.as_deref()) {
+            Logger::warning_logger(handler_name, log_id, "oidc_authenticate_client_services.verify_secret", &credentials.client_id);
+            return Err(OAuthError::new("invalid_client", "client authentication failed"));
+        }
+        Ok(client)
+    }
+
+    async fn exchange_code(
+        log_id: &str,
+        client: &OAuthClientRow,
+        request: TokenRequest,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<TokenPayload, OAuthError> {
+        let handler_name = "oidc_exchange_code_services";
+        let code = request.code.ok_or_else(|| OAuthError::new("invalid_request", "code is required"))?;
+        let code_verifier = request.code_verifier.ok_or_else(|| OAuthError::new("invalid_request", "code_verifier is required"))?;
+
+        let stored = take_one_time(redis_pool, &code_key(&code)).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_exchange_code_services.take_code", &error);
+            AuthError::Upstream(error)
+        })?;
+        let authorization_code: AuthorizationCode = stored.as_deref()
+            .and_then(|value| serde_json::from_str(value).ok())
+            .ok_or_else(|| OAuthError::new("invalid_grant", "invalid or expired authorization code"))?;
+
+        if authorization_code.client_id != client.client_id || request.redirect_uri.as_deref() != Some(authorization_code.redirect_uri.as_str()) {
+            Logger::warning_logger(handler_name, log_id, "oidc_exchange_code_services.validate_code", "client or redirect_uri mismatch");
+            return Err(OAuthError::new("invalid_grant", "authorization code was issued for another client or redirect_uri"));
+        }
+        if !constant_time_eq(&code_challenge(&code_verifier), &authorization_code.code_challenge) {
+            Logger::warning_logger(handler_name, log_id, "oidc_exchange_code_services.validate_code", "code_verifier mismatch");
+            return Err(OAuthError::new("invalid_grant", "code_verifier does not match the code_challenge"));
+        }
+
+        let grant = UserGrant {
+            user_id: authorization_code.user_id,
+            granted: authorization_code.scopes.clone(),
+            scopes: authorization_code.scopes,
+            nonce: authorization_code.nonce,
+            family_id: Uuid::new_v4(),
+        };
+        Self::issue_user_tokens(log_id, client, grant, db_pool, jwt_keyring).await
+    }
+
+    async fn refresh(
+        log_id: &str,
+        client: &OAuthClientRow,
+        request: TokenRequest,
+        db_pool: &DbPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<TokenPayload, OAuthError> {
+        let handler_name = "oidc_refresh_services";
+        let token = request.refresh_token.ok_or_else(|| OAuthError::new("invalid_request", "refresh_token is required"))?;
+        let decoded = decode_refresh_token(&token, jwt_keyring)
+            .map_err(|error| OAuthError::new("invalid_grant", format!("invalid refresh token: {}", error)))?;
+        let user_id = decoded.claims.token.id;
+
+        let stored_token = OidcQuery::find_client_refresh_token(&token, user_id, &client.client_id, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_refresh_services.find_refresh_token", &error.to_string());
+        })?;
+
+        let consumed = match stored_token.used_at {
+            Some(_) => false,
+            None => UserQuery::consume_refresh_token(stored_token.id, db_pool).await?,
+        };
+        if !consumed {
+            Logger::warning_logger(handler_name, log_id, "oidc_refresh_services.reuse_detected", &client.client_id);
+            if let Err(error) = UserQuery::delete_refresh_token_family(stored_token.family_id, db_pool).await {
+                Logger::warning_logger(handler_name, log_id, "oidc_refresh_services.delete_refresh_token_family", &error.to_string());
+            }
+            return Err(OAuthError::new("invalid_grant", "refresh token reused, session revoked"));
+        }
+
+        let granted = parse_scope(stored_token.scope.as_deref());
+        let scopes = match request.scope.as_deref() {
+            Some(scope) => parse_scope(Some(scope)),
+            None => granted.clone(),
+        };
+        if !scopes.iter().all(|scope| granted.contains(scope)) {
+            return Err(OAuthError::new("invalid_scope", "scope exceeds the scope originally granted"));
+        }
+
+        let grant = UserGrant { user_id, scopes, granted, nonce: None, family_id: stored_token.family_id };
+        Self::issue_user_tokens(log_id, client, grant, db_pool, jwt_keyring).await
+    }
+
+    fn client_credentials(
+        log_id: &str,
+        client: &OAuthClientRow,
+        request: TokenRequest,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<TokenPayload, OAuthError> {
+        let handler_name = "oidc_client_credentials_services";
+        if client.client_secret_hash.is_none() {
+            return Err(OAuthError::new("unauthorized_client", "public clients cannot use client_credentials"));
+        }
+
+        let scopes = match request.scope.as_deref() {
+            Some(scope) => parse_scope(Some(scope)),
+            None => client.scopes.iter().filter(|scope| !USER_SCOPES.contains(&scope.as_str())).cloned().collect(),
+        };
+        if scopes.iter().any(|scope| USER_SCOPES.contains(&scope.as_str())) || !client.allows_scopes(&scopes) {
+            return Err(OAuthError::new("invalid_scope", "scope must only contain service scopes registered for the client"));
+        }
+
+        let access_token = generate_service_token(ServiceToken { client_id: client.client_id.clone(), scopes: scopes.clone() }, jwt_keyring)
+            .map_err(|error| server_error("generating service token", error))?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_client_credentials_services.generate_service_token");
+        Ok(TokenPayload {
+            access_token,
+            token_type: String::from("Bearer"),
+            expires_in: jwt_keyring.lifetimes().service_token,
+            refresh_token: None,
+            id_token: None,
+            scope: scopes.join(" "),
+        })
+    }
+
+    async fn issue_user_tokens(
+        log_id: &str,
+        client: &OAuthClientRow,
+        grant: UserGrant,
+        db_pool: &DbPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<TokenPayload, OAuthError> {
+        let handler_name = "oidc_issue_user_tokens_services";
+        let user = match OAuthQuery::find_login_user(grant.user_id, db_pool).await? {
+            Some(user) if !user.disabled => user,
+            _ => return Err(OAuthError::new("invalid_grant", "user not found or disabled")),
+        };
+        let has_scope = |name: &str| grant.scopes.iter().any(|scope| scope == name);
+
+        let id_token = match has_scope(SCOPE_OPENID) {
+            true => {
+                let data = IdToken {
+                    sub: user.id.to_string(),
+                    nonce: grant.nonce.clone(),
+                    auth_time: None,
+                    email: has_scope(SCOPE_EMAIL).then(|| user.email.clone()),
+                    email_verified: has_scope(SCOPE_EMAIL).then_some(user.email_verified),
+                    preferred_username: has_scope(SCOPE_PROFILE).then(|| user.username.clone()),
+                };
+                Some(generate_id_token(data, &client.client_id, jwt_keyring).map_err(|error| server_error("generating id token", error))?)
+            },
+            false => None,
+        };
+
+        let refresh_token = match client.allows_grant(GRANT_REFRESH_TOKEN) {
+            true => {
+                let refresh_token = generate_refresh_token(RefreshToken { id: user.id }, jwt_keyring)
+                    .map_err(|error| server_error("generating refresh token", error))?;
+                let expires_at = Utc::now() + chrono::Duration::seconds(jwt_keyring.lifetimes().refresh_token);
+                OidcQuery::create_client_refresh_token(&refresh_token, user.id, grant.family_id, expires_at, &client.client_id, &grant.granted.join(" "), db_pool).await.inspect_err(|error|{
+                    Logger::warning_logger(handler_name, log_id, "oidc_issue_user_tokens_services.save_refresh_token", &error.to_string());
+                })?;
+                Some(refresh_token)
+            },
+            false => None,
+        };
+
+        let access_token = generate_client_access_token(ClientAccessToken {
+            id: user.id,
+            client_id: client.client_id.clone(),
+            scopes: grant.scopes.clone(),
+        }, jwt_keyring).map_err(|error| server_error("generating access token", error))?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_issue_user_tokens_services.success");
+        Ok(TokenPayload {
+            access_token,
+            token_type: String::from("Bearer"),
+            expires_in: jwt_keyring.lifetimes().access_token,
+            refresh_token,
+            id_token,
+            scope: grant.scopes.join(" "),
+        })
+    }
+
+    // Only answers client access tokens; the claims returned follow the scopes granted to that client.
+    pub async fn userinfo(
+        log_id: &str,
+        access_token: &str,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<UserInfoPayload, AuthError> {
+        let handler_name = "oidc_userinfo_services";
+        let claims = decode_client_access_token(access_token, jwt_keyring).map_err(|err|{
+            let error = token_error(err);
+            Logger::warning_logger(handler_name, log_id, "oidc_userinfo_services.decode_token", &error.to_string());
+            error
+        })?.claims;
+
+        if is_token_revoked(redis_pool, &claims.jti, &claims.sub, claims.iat).map_err(AuthError::Upstream)? {
+            return Err(token_error(JwtLibError::Revoked));
+        }
+        if is_subject_disabled(redis_pool, &claims.sub).map_err(AuthError::Upstream)? {
+            return Err(AuthError::Forbidden(String::from("account disabled")));
+        }
+        if !claims.token.has_scope(SCOPE_OPENID) {
+            return Err(AuthError::Forbidden(String::from("insufficient scope: openid is required")));
+        }
+        // Deleting a client has to end its users' sessions as well, and client tokens cannot be revoked by subject.
+        if OidcQuery::find_client(&claims.token.client_id, db_pool).await?.is_none() {
+            return Err(AuthError::Unauthorized(String::from("client no longer exists")));
+        }
+
+        match OidcQuery::find_userinfo(claims.token.id, db_pool).await {
+            Ok(Some(user)) => {
+                Logger::info_logger(handler_name, log_id, "oidc_userinfo_services.query_db");
+                Ok(scoped_userinfo(user, &claims.token))
+            },
+            Ok(None) => Err(AuthError::NotFound(String::from("user not found"))),
+            Err(error) => {
+                Logger::warning_logger(handler_name, log_id, "oidc_userinfo_services.query_db", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub fn discovery(
+        oidc: &Oidc,
+        jwt_keyring: &JwtKeyring
+    ) -> Value {
+        let base_url = oidc.base_url.trim_end_matches('/');
+        let signing_algorithms: Vec<String> = jwt_keyring.signing_key().map(|key| format!("{:?}", key.algorithm)).into_iter().collect();
+
+        json!({
+            "issuer": jwt_keyring.claims().issuer,
+            "authorization_endpoint": format!("{}/api/oauth/authorize", base_url),
+            "token_endpoint": format!("{}/api/oauth/token", base_url),
+            "userinfo_endpoint": format!("{}/api/oauth/userinfo", base_url),
+            "jwks_uri": format!("{}/.well-known/jwks.json", base_url),
+            "response_types_supported": ["code"],
+            "grant_types_supported": SUPPORTED_GRANTS,
+            "subject_types_supported": ["public"],
+            "id_token_signing_alg_values_supported": signing_algorithms,
+            "scopes_supported": USER_SCOPES,
+            "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post", "none"],
+            "code_challenge_methods_supported": ["S256"],
+            "claims_supported": ["sub", "iss", "aud", "exp", "iat", "nonce", "email", "email_verified", "preferred_username"]
+        })
+    }
+
+    pub async fn create_client(
+        log_id: &str,
+        actor: &AccessToken,
+        data: CreateClientData,
+        db_pool: &DbPool
+    ) -> Result<CreatedClientPayload, AuthError> {
+        let handler_name = "oidc_create_client_services";
+        if let Some(grant) = data.grant_types.iter().find(|grant| !SUPPORTED_GRANTS.contains(&grant.as_str())) {
+            return Err(AuthError::validation(format!("unsupported grant type: {}", grant)));
+        }
+        if data.public && data.grant_types.iter().any(|grant| grant == GRANT_CLIENT_CREDENTIALS) {
+            return Err(AuthError::validation("public clients cannot use client_credentials"));
+        }
+        if data.grant_types.iter().any(|grant| grant == GRANT_AUTHORIZATION_CODE) && data.redirect_uris.is_empty() {
+            return Err(AuthError::validation("authorization_code clients need at least one redirect uri"));
+        }
+        for redirect_uri in &data.redirect_uris {
+            match Url::parse(redirect_uri) {
+                Ok(url) if url.fragment().is_none() => {},
+                _ => return Err(AuthError::validation(format!("invalid redirect uri: {}", redirect_uri))),
+            }
+        }
+        if data.scopes.iter().any(|scope| scope.is_empty() || scope.contains(char::is_whitespace)) {
+            return Err(AuthError::validation("scopes must be non-empty and contain no whitespace"));
+        }
+
+        let client_secret = (!data.public).then(random_token);
+        let details = json!({ "request_id": data.request_id, "client_id": data.client_id, "grant_types": data.grant_types, "scopes": data.scopes });
+
+        let client = OidcQuery::create_client(actor.id, &data, client_secret.as_deref().map(hash_token), details, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_create_client_services.insert_client", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_create_client_services.insert_client");
+        Ok(CreatedClientPayload { client: client.into(), client_secret })
+    }
+
+    pub async fn list_clients(
+        log_id: &str,
+        db_pool: &DbPool
+    ) -> Result<Vec<ClientPayload>, AuthError> {
+        let handler_name = "oidc_list_clients_services";
+        let clients = OidcQuery::list_clients(db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "oidc_list_clients_services.query_db", &error.to_string());
+        })?;
+
+        Logger::info_logger(handler_name, log_id, "oidc_list_clients_services.query_db");
+        Ok(clients.into_iter().map(ClientPayload::from).collect())
+    }
+
+    // Deleting a client cascades to every refresh token it holds.
+    pub async fn delete_client(
+        log_id: &str,
+        actor: &AccessToken,
+        client_id: &str,
+        data: AdminActionData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<(), AuthError> {
+        let handler_name = "oidc_delete_client_services";
+        let details = json!({ "request_id": data.request_id, "reason": data.reason, "client_id": client_id });
+        match OidcQuery::delete_client(actor.id, client_id, details, db_pool).await {
+            Ok(true) => {
+                Logger::info_logger(handler_name, log_id, "oidc_delete_client_services.delete_client");
+                // Service tokens carry the client_id as subject, so outstanding ones die with the client.
+                revoke_subject_tokens(redis_pool, client_id, Utc::now().timestamp(), jwt_keyring.lifetimes().service_token)
+                    .map_err(AuthError::Upstream)
+                    .inspect_err(|error| Logger::err_logger(handler_name, log_id, "oidc_delete_client_services.revoke_subject_tokens", error))
+            },
+            Ok(false) => Err(AuthError::NotFound(String::from("client not found"))),
+            Err(error) => {
+                Logger::warning_logger(handler_name, log_id, "oidc_delete_client_services.delete_client", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jwt_libs::{decode_access_token, decode_service_token, generate_access_token, testing::{access_token, keyring}};
+    use redis_libs::testing::test_redis_pool;
+
+    use super::*;
+
+    fn client(secret: Option<&str>) -> OAuthClientRow {
+        OAuthClientRow {
+            id: Uuid::new_v4(),
+            client_id: String::from("post_gateway"),
+            client_secret_hash: secret.map(hash_token),
+            name: String::from("post gateway"),
+            redirect_uris: Vec::new(),
+            grant_types: vec![String::from(GRANT_CLIENT_CREDENTIALS)],
+            scopes: vec![String::from("openid"), String::from("post:read")],
+            created_at: Utc::now(),
+        }
+    }
+
+    fn token_request(scope: Option<&str>) -> TokenRequest {
+        TokenRequest {
+            grant_type: String::from(GRANT_CLIENT_CREDENTIALS),
+            code: None,
+            redirect_uri: None,
+            code_verifier: None,
+            refresh_token: None,
+            scope: scope.map(String::from),
+            client_id: None,
+            client_secret: None,
+        }
+    }
+
+    #[test]
+    fn parses_scope_and_builds_redirects() {
+        assert_eq!(parse_scope(Some("openid  email openid")), vec!["openid", "email"]);
+        assert!(parse_scope(None).is_empty());
+
+        let redirect = redirect_uri_with("https://tool.local/callback?x=1", &[("error", "invalid_scope")], Some("s 1")).unwrap();
+        assert_eq!(redirect, "https://tool.local/callback?x=1&error=invalid_scope&state=s+1");
+    }
+
+    #[test]
+    fn matches_client_secret() {
+        assert!(client_secret_matches(&client(Some("secret")), Some("secret")));
+        assert!(!client_secret_matches(&client(Some("secret")), Some("other")));
+        assert!(!client_secret_matches(&client(Some("secret")), None));
+        assert!(client_secret_matches(&client(None), None));
+        assert!(!client_secret_matches(&client(None), Some("secret")));
+    }
+
+    #[test]
+    fn issuer_must_be_the_discovery_url() {
+        assert!(validate_issuer("https://auth.example.com", "https://auth.example.com/").is_ok());
+        assert!(validate_issuer("http://localhost:8080", "http://localhost:8080").is_ok());
+        assert!(validate_issuer("http://localhost:8080", "auth_services").is_err());
+        assert!(validate_issuer("http://auth.example.com", "http://auth.example.com").is_err());
+    }
+
+    #[test]
+    fn client_credentials_issue_service_tokens() {
+        let keyring = keyring();
+        let payload = OidcServices::client_credentials("test", &client(Some("secret")), token_request(None), &keyring).unwrap();
+        assert_eq!(payload.scope, "post:read");
+        assert!(payload.refresh_token.is_none() && payload.id_token.is_none());
+
+        let claims = decode_service_token(&payload.access_token, &keyring).unwrap().claims;
+        assert_eq!(claims.sub, "post_gateway");
+        assert!(claims.token.has_scope("post:read"));
+
+        let user_scope = OidcServices::client_credentials("test", &client(Some("secret")), token_request(Some("openid")), &keyring);
+        assert_eq!(user_scope.unwrap_err().error, "invalid_scope");
+        let public = OidcServices::client_credentials("test", &client(None), token_request(None), &keyring);
+        assert_eq!(public.unwrap_err().error, "unauthorized_client");
+    }
+
+    fn authorize_query(prompt: Option<&str>) -> AuthorizeQuery {
+        AuthorizeQuery {
+            response_type: String::from("code"),
+            client_id: String::from("tool"),
+            redirect_uri: String::from("https://tool.local/callback"),
+            scope: Some(String::from("openid email")),
+            state: Some(String::from("s1")),
+            nonce: None,
+            code_challenge: Some(code_challenge("verifier")),
+            code_challenge_method: Some(String::from("S256")),
+            prompt: prompt.map(String::from),
+        }
+    }
+
+    fn param(redirect: &str, name: &str) -> Option<String> {
+        Url::parse(redirect).unwrap().query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned())
+    }
+
+    #[test]
+    fn authorize_sends_the_user_through_login_and_consent() {
+        let (keyring, redis_pool) = (keyring(), test_redis_pool());
+        let oidc = Oidc {
+            base_url: String::from("http://localhost:8080/"),
+            code_lifetime: 60,
+            login_url: String::from("http://localhost:8080/login"),
+            consent_url: String::from("http://localhost:8080/consent"),
+            session_lifetime: 3600,
+            consent_lifetime: 600,
+        };
+        let scopes = || vec![String::from("openid"), String::from("email")];
+        let authorize = |user_id: Option<Uuid>, consented: &[String], prompt: Option<&str>| {
+            OidcServices::authorize_user("test", user_id, consented, authorize_query(prompt), scopes(), "client_id=tool", &oidc, &redis_pool)
+        };
+
+        let login = authorize(None, &[], None).unwrap();
+        assert!(login.starts_with("http://localhost:8080/login?"));
+        assert_eq!(param(&login, "return_to").as_deref(), Some("http://localhost:8080/api/oauth/authorize?client_id=tool"));
+        assert_eq!(param(&authorize(None, &[], Some(PROMPT_NONE)).unwrap(), "error").as_deref(), Some("login_required"));
+
+        let token = generate_access_token(access_token().build(), &keyring).unwrap();
+        let claims = decode_access_token(&token, &keyring).unwrap().claims;
+        let session = OidcServices::create_session("test", &claims, &redis_pool, &keyring).unwrap();
+        let user_id = OidcServices::session_user("test", Some(&session), oidc.session_lifetime, &redis_pool, &keyring).unwrap();
+        assert_eq!(user_id, Some(claims.token.id));
+        assert_eq!(OidcServices::session_user("test", Some("unknown"), oidc.session_lifetime, &redis_pool, &keyring).unwrap(), None);
+
+        let consent = authorize(user_id, &[String::from("openid")], None).unwrap();
+        assert!(consent.starts_with("http://localhost:8080/consent?"));
+        let stored = fetch_value(&redis_pool, &consent_key(&param(&consent, "consent_id").unwrap())).unwrap();
+        assert_eq!(OidcServices::pending_consent(stored.as_deref(), claims.token.id).unwrap().scopes, scopes());
+        assert_eq!(OidcServices::pending_consent(stored.as_deref(), Uuid::new_v4()).unwrap_err(), AuthError::Forbidden(String::from("consent belongs to another user")));
+        assert_eq!(param(&authorize(user_id, &[], Some(PROMPT_NONE)).unwrap(), "error").as_deref(), Some("consent_required"));
+
+        let code = authorize(user_id, &scopes(), None).unwrap();
+        assert!(code.starts_with("https://tool.local/callback?"));
+        assert!(param(&code, "code").is_some() && param(&code, "state").as_deref() == Some("s1"));
+        assert!(authorize(user_id, &scopes(), Some(PROMPT_CONSENT)).unwrap().starts_with("http://localhost:8080/consent?"));
+    }
+
+    #[test]
+    fn userinfo_follows_the_granted_scopes() {
+        let user = || UserInfoRow { id: Uuid::new_v4(), username: String::from("tester"), email: String::from("tester@mail.com"), email_verified: true };
+        let token = |scopes: &[&str]| ClientAccessToken { id: Uuid::new_v4(), client_id: String::from("tool"), scopes: scopes.iter().map(|scope| scope.to_string()).collect() };
+
+        let openid = scoped_userinfo(user(), &token(&["openid"]));
+        assert!(openid.preferred_username.is_none() && openid.email.is_none() && openid.email_verified.is_none());
+
+        let email = scoped_userinfo(user(), &token(&["openid", "email"]));
+        assert_eq!((email.email.as_deref(), email.email_verified), (Some("tester@mail.com"), Some(true)));
+        assert!(email.preferred_username.is_none());
+
+        let profile = scoped_userinfo(user(), &token(&["openid", "profile"]));
+        assert_eq!(profile.preferred_username.as_deref(), Some("tester"));
+        assert!(profile.email.is_none());
+    }
+
+    #[test]
+    fn discovery_points_at_our_endpoints() {
+        let document = OidcServices::discovery(&Oidc { base_url: String::from("https://auth.local/"), code_lifetime: 60, ..Default::default() }, &keyring());
+        assert_eq!(document["issuer"], "auth_services");
+        assert_eq!(document["token_endpoint"], "https://auth.local/api/oauth/token");
+        assert_eq!(document["id_token_signing_alg_values_supported"][0], "HS256");
+    }
+}
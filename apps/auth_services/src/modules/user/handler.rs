@@ -1,207 +1,520 @@
-use actix_web::{get, post, web::{scope, Data, Json, ServiceConfig}, HttpMessage, HttpRequest, HttpResponse, Responder};
+use actix_web::{delete, get, http::header::USER_AGENT, patch, post, web::{scope, Data, Json, Path, Query, ServiceConfig}, HttpMessage, HttpRequest, HttpResponse};
 use logger_libs::Logger;
 use serde::Serialize;
 use serde_json::json;
+use uuid::Uuid;
 use validator::Validate;
-use std::{borrow::Cow, collections::HashMap, fmt::Debug, time::Instant};
-use jwt_libs::types::AccessToken;
-use crate::{middlewares::{access_token_middleware::AccessTokenMW, refresh_token_middleware::RefreshTokenMW},AppState};
+use std::{collections::HashMap, fmt::Debug, time::Instant};
+use jwt_libs::types::{AccessToken, TokenClaims};
+use crate::{error::{log_failure, AuthError}, middlewares::{access_token_middleware::AccessTokenMW, authorization::AuthUser, refresh_token_middleware::RefreshTokenMW}, modules::oauth::handler::{oauth_callback_handler, oauth_start_handler}, AppState};
 
-use super::{model::{LoginData, RegisterData}, service::UserServices};
+use super::{model::{ChangePasswordData, DeleteAccountData, ForgotPasswordData, LoginData, LoginResult, MfaVerifyData, RegisterData, ResetPasswordData, SessionMetadata, TotpConfirmData, UpdateProfileData, VerifyEmailQuery}, service::{login_failure_reason, login_identifier, UserServices}};
 
-fn json_validate<T>(
+// The socket peer, never X-Forwarded-For: the ip keys the login rate limit and must not be client controlled.
+pub fn session_metadata(req: &HttpRequest) -> SessionMetadata {
+    SessionMetadata {
+        user_agent: req.headers().get(USER_AGENT).and_then(|value| value.to_str().ok()).map(String::from),
+        ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+    }
+}
+
+pub fn json_validate<T>(
     json_data: Json<T>
-) -> Result<T, HttpResponse>
-where 
+) -> Result<T, AuthError>
+where
     T: Clone + Serialize + Debug + Validate
 {
     let data = json_data.into_inner();
-    let mut error_map: HashMap<String,Cow<'static,str>> = HashMap::new();
-    
+    let mut error_map: HashMap<String,Vec<String>> = HashMap::new();
+
     if let Err(errors) = data.validate() {
         for (field, error) in errors.field_errors() {
-            let error_messages = error.into_iter().map(|e| {
-                e.message.clone()
-            });
-            
-            for error_message in error_messages {
-                    error_map.insert(field.to_string(), error_message.clone().unwrap());
-            }
+            let messages = error.iter().filter_map(|e| e.message.as_ref().map(|message| message.to_string()));
+            error_map.entry(field.to_string()).or_default().extend(messages);
         }
 
-        return Err(HttpResponse::BadRequest().json(json!({
-            "status": "failed",
-            "message": error_map
-        })));
+        return Err(AuthError::Validation {
+            message: String::from("invalid request body"),
+            fields: error_map
+        });
     }
 
     Ok(data)
 }
 
+pub fn access_claims(req: &HttpRequest) -> Result<TokenClaims<AccessToken>, AuthError> {
+    req.extensions().get::<TokenClaims<AccessToken>>().cloned().ok_or(AuthError::Unauthorized(String::from("token not found")))
+}
+
+pub fn login_response(result: LoginResult) -> HttpResponse {
+    match result{
+        LoginResult::Authenticated(payload)=>HttpResponse::Ok().json(json!({
+            "status":"success",
+            "message":"login successfull",
+            "data":payload
+        })),
+        LoginResult::MfaRequired(payload)=>HttpResponse::Ok().json(json!({
+            "status":"mfa_required",
+            "message":"two-factor verification required",
+            "data":payload
+        }))
+    }
+}
+
 #[post("/register")]
 async fn register_handlers(
     register_body: Json<RegisterData>,
     app_data: Data<AppState>
-) -> impl Responder {
+) -> Result<HttpResponse, AuthError> {
     let start = Instant::now();
     let handler_name= "register_handler";
 
-    let register_data = match json_validate(register_body) {
-        Ok(validated_data) => {
-            validated_data
-        },
-        Err(err_response) => {
-            return err_response
-        }
-    };
-
-    let log_id = format!("{}",register_data.request_id);
+    let register_data = json_validate(register_body)?;
+    let log_id = register_data.request_id.to_string();
 
-    match UserServices::register(
+    let user_payload = UserServices::register(
         &log_id,
         register_data,
+        app_data.mail.verification_token_lifetime,
         &app_data.db,
         &app_data.rabbit
-    ).await {
-        Ok(user_payload) => {
-            let end:Instant = Instant::now();
-            Logger::info_logger(handler_name,&log_id, &format!("user_register.{:?}",start - end));
-            HttpResponse::Created().json(json!({
-                "status": "success",
-                "message": "Registration successful",
-                "data": user_payload
-            }))
-        },
-        Err(error) => {
-            if error.contains("input error"){
-                Logger::warning_logger(handler_name, &log_id, "register.db_user_input",&error);
-                HttpResponse::BadRequest().json(json!({
-                    "status": "failed",
-                    "message": format!("{}", error)
-                }))
-            }else {
-                Logger::warning_logger(handler_name, &log_id, "register.db_user_input",&error);
-                HttpResponse::BadGateway().json(json!({
-                    "status": "failed",
-                    "message": format!("{}", error)
-                }))
-            }
-        }
-    }
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "register.db_user_input", error))?;
+
+    let end:Instant = Instant::now();
+    Logger::info_logger(handler_name,&log_id, &format!("user_register.{:?}",start - end));
+    Ok(HttpResponse::Created().json(json!({
+        "status": "success",
+        "message": "Registration successful",
+        "data": user_payload
+    })))
 }
 
 #[post("/login")]
 async fn login_handlers(
+    req: HttpRequest,
     login_body: Json<LoginData>,
     app_data: Data<AppState>
-) -> impl Responder{
+) -> Result<HttpResponse, AuthError> {
     let handler_name= "login_handler";
     let start = Instant::now();
-    let log_id = format!("{}",login_body.request_id);
+    let log_id = login_body.request_id.to_string();
 
     let login_data = login_body.into_inner();
+    let identifier = login_identifier(&login_data);
+    let metadata = session_metadata(&req);
+
+    UserServices::check_login_limit(&log_id, &identifier, metadata.ip.as_deref(), &app_data.login_limit, &app_data.redis)
+        .map_err(|error| log_failure(handler_name, &log_id, "login_handler.check_login_limit", error))?;
 
-    match UserServices::login(
+    let result = match UserServices::login(
         &log_id,
-        login_data.clone(),
-        &app_data.db, 
-        &app_data.redis
+        login_data,
+        &metadata,
+        &app_data.db,
+        &app_data.jwt
     ).await{
-        Ok(payload)=>{
-            let end = Instant::now();
-            Logger::info_logger(handler_name,&log_id, &format!("login_handler.{:?}", end - start));
-            HttpResponse::Ok().json(json!({
-                "status":"success",
-                "message":"login successfull",
-                "data":payload
-            }))
-        },
-        Err(errors)=>{
-            Logger::warning_logger(handler_name, &log_id, "login_handler.failed", &errors);
-            HttpResponse::BadGateway().json(json!({
-                "status":"failed",
-                "message":format!("server Error: {}",errors)
-            }))
+        Ok(result)=>result,
+        Err(error)=>{
+            Logger::warning_logger(handler_name, &log_id, "login_handler.failed", &error.to_string());
+            if let Some(reason) = login_failure_reason(&error){
+                match UserServices::record_login_failure(&log_id, &identifier, reason, &metadata, &app_data.login_limit, &app_data.db, &app_data.redis).await{
+                    Ok(Some(locked))=>return Err(locked),
+                    Ok(None)=>{},
+                    Err(record_error)=>{
+                        Logger::err_logger(handler_name, &log_id, "login_handler.record_login_failure", record_error.to_string());
+                    }
+                }
+            }
+            return Err(error)
         }
+    };
+
+    if let Err(error) = UserServices::reset_login_failures(&identifier, &app_data.redis){
+        Logger::warning_logger(handler_name, &log_id, "login_handler.reset_login_failures", &error.to_string());
     }
+    let end = Instant::now();
+    Logger::info_logger(handler_name,&log_id, &format!("login_handler.{:?}", end - start));
+
+    Ok(login_response(result))
+}
+
+#[post("/2fa/verify")]
+async fn verify_mfa_handler(
+    req: HttpRequest,
+    verify_body: Json<MfaVerifyData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "verify_mfa_handler";
+    let start = Instant::now();
+    let verify_data = verify_body.into_inner();
+    let log_id = verify_data.request_id.to_string();
+
+    let payload = UserServices::verify_mfa(
+        &log_id,
+        verify_data,
+        &session_metadata(&req),
+        &app_state.login_limit,
+        &app_state.secret_cipher,
+        &app_state.db,
+        &app_state.redis,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "verify_mfa_handler.failed", error))?;
+
+    let end = Instant::now();
+    Logger::info_logger(handler_name, &log_id, &format!("verify_mfa_handler.{:?}", end - start));
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"login successfull",
+        "data":payload
+    })))
+}
+
+#[get("/verify_email")]
+async fn verify_email_handler(
+    query: Query<VerifyEmailQuery>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "verify_email_handler";
+    let log_id = format!("{} User.Verify_email",chrono::Utc::now());
+
+    let user_id = UserServices::verify_email(&log_id, &query.token, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "verify_email.query_db", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"email verified",
+        "data":{
+            "id":user_id
+        }
+    })))
+}
+
+#[post("/password/forgot")]
+async fn forgot_password_handler(
+    forgot_body: Json<ForgotPasswordData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "forgot_password_handler";
+
+    let forgot_data = json_validate(forgot_body)?;
+    let log_id = forgot_data.request_id.to_string();
+
+    UserServices::forgot_password(
+        &log_id,
+        forgot_data,
+        app_state.mail.reset_token_lifetime,
+        &app_state.db,
+        &app_state.rabbit
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "forgot_password.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"if the email is registered, a reset link has been sent"
+    })))
+}
+
+#[post("/password/reset")]
+async fn reset_password_handler(
+    reset_body: Json<ResetPasswordData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "reset_password_handler";
+
+    let reset_data = json_validate(reset_body)?;
+    let log_id = reset_data.request_id.to_string();
+
+    UserServices::reset_password(&log_id, reset_data, &app_state.db, &app_state.redis, &app_state.jwt).await
+        .map_err(|error| log_failure(handler_name, &log_id, "reset_password.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"password has been reset"
+    })))
+}
+
+#[post("/logout", wrap = "AccessTokenMW")]
+async fn logout_handler(
+    req: HttpRequest,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "logout_handler";
+    let log_id = format!("{} User.Logout",chrono::Utc::now());
+
+    let refresh_token = req.headers().get("refresh-token")
+        .and_then(|token| token.to_str().ok())
+        .map(String::from)
+        .ok_or(AuthError::validation("refresh token not found"))
+        .map_err(|error| log_failure(handler_name, &log_id, "logout.get_refresh_token", error))?;
+    let claims = access_claims(&req)?;
+
+    UserServices::logout(&log_id, &refresh_token, &claims, &app_state.db, &app_state.redis).await
+        .map_err(|error| log_failure(handler_name, &log_id, "logout.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"logout success"
+    })))
+}
+
+#[post("/logout_all", wrap = "AccessTokenMW")]
+async fn logout_all_handler(
+    req: HttpRequest,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "logout_all_handler";
+    let log_id = format!("{} User.Logout_all",chrono::Utc::now());
+    let claims = access_claims(&req)?;
+
+    let sessions = UserServices::logout_all(&log_id, &claims, &app_state.db, &app_state.redis).await
+        .map_err(|error| log_failure(handler_name, &log_id, "logout_all.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"logout from all sessions success",
+        "data":{
+            "sessions":sessions
+        }
+    })))
 }
 
 #[get("/refresh_token")]
 async fn refresh_token_handler(
     req:HttpRequest,
     app_state:Data<AppState>,
-) -> impl Responder{
+) -> Result<HttpResponse, AuthError>{
     let handler_name= "refresh_token";
     let start = Instant::now();
     let log_id = format!("{} User.Refresh_token",chrono::Utc::now());
 
-    let token = match req.extensions().get::<String>().clone(){
-        Some(token)=>token.to_string(),
-        None=>{
-            let error_message = "token not found";
-            Logger::warning_logger(handler_name, &log_id, "refresh_token.get_token_midleware", error_message);
-            return HttpResponse::BadRequest().json(json!({
-                "status":"error",
-                "message": error_message
-            }))
-        }
-    };
-    
+    let token = req.extensions().get::<String>().cloned()
+        .ok_or(AuthError::validation("token not found"))
+        .map_err(|error| log_failure(handler_name, &log_id, "refresh_token.get_token_midleware", error))?;
 
-    match UserServices::refresh_token(
+    let payload = UserServices::refresh_token(
         &log_id,
         token,
+        &session_metadata(&req),
         &app_state.db,
-        &app_state.redis
-    ).await{
-        Ok(access_token)=>{
-            let end = Instant::now();
-            Logger::info_logger(handler_name,&log_id,&format!("access token create, request time : {:?}",end - start));
-            HttpResponse::Ok().json(json!({
-                "status":"success",
-                "message":"get token success",
-                "data":{
-                    "access_token":format!("{}",access_token)
-                }
-            }))
-        },
-        Err(error)=>{
-            HttpResponse::BadGateway().json(json!({
-                "status":"failed",
-                "message":format!("{}",error)
-            }))
-        }
-    }
+        &app_state.jwt
+    ).await?;
+
+    let end = Instant::now();
+    Logger::info_logger(handler_name,&log_id,&format!("access token create, request time : {:?}",end - start));
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get token success",
+        "data":payload
+    })))
+}
+
+#[get("/.well-known/jwks.json")]
+async fn jwks_handler(
+    app_state: Data<AppState>
+)-> HttpResponse{
+    HttpResponse::Ok().json(app_state.jwt.jwks())
 }
 
 #[get("/user_profile")]
 async fn user_profile_handler(
-    req: HttpRequest,
+    user: AuthUser,
     app_state: Data<AppState>
-)-> impl Responder{
+)-> Result<HttpResponse, AuthError>{
     let start = Instant::now();
     let log_id = format!("{} User.Refresh_token",chrono::Utc::now());
-    let token = req.extensions().get::<AccessToken>().cloned().expect("token not found");
     let handler_name = "find_user_handler";
 
-    match UserServices::find_user_login(&log_id,token, &app_state.db).await{
-        Ok(user)=>{
-            let end = Instant::now();
-            Logger::info_logger(handler_name, &log_id, &format!("get_user_login.{:?}",start-end));
-            HttpResponse::Ok().json(json!({
-                "status":"success",
-                "message":"get user profile success",
-                "data":user
-            }))
-        },
-        Err(error)=>{
-            Logger::warning_logger(handler_name, &log_id, "get_user_login.query_db", &error);
-            HttpResponse::BadGateway().json(json!({
-                "status":"failed",
-                "message": format!("server error: {}",error)
-            }))
+    let user = UserServices::find_user_login(&log_id,user.0, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "get_user_login.query_db", error))?;
+
+    let end = Instant::now();
+    Logger::info_logger(handler_name, &log_id, &format!("get_user_login.{:?}",start-end));
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get user profile success",
+        "data":user
+    })))
+}
+
+#[post("/revoke_token")]
+async fn revoke_token_handler(
+    req: HttpRequest,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "revoke_token_handler";
+    let log_id = format!("{} User.Revoke_token",chrono::Utc::now());
+    let claims = access_claims(&req)?;
+
+    UserServices::revoke_access_token(&log_id, &claims, &app_state.redis)
+        .map_err(|error| log_failure(handler_name, &log_id, "revoke_token.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"token revoked"
+    })))
+}
+
+#[get("/sessions")]
+async fn list_sessions_handler(
+    user: AuthUser,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "list_sessions_handler";
+    let log_id = format!("{} User.Sessions",chrono::Utc::now());
+
+    let sessions = UserServices::list_sessions(&log_id, &user, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "list_sessions.query_db", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"get sessions success",
+        "data":sessions
+    })))
+}
+
+#[delete("/sessions/{session_id}")]
+async fn revoke_session_handler(
+    user: AuthUser,
+    path: Path<Uuid>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "revoke_session_handler";
+    let session_id = path.into_inner();
+    let log_id = format!("{} User.Sessions.{}",chrono::Utc::now(),session_id);
+
+    UserServices::revoke_session(&log_id, session_id, &user, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "revoke_session.query_db", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"session revoked"
+    })))
+}
+
+#[patch("/profile")]
+async fn update_profile_handler(
+    user: AuthUser,
+    profile_body: Json<UpdateProfileData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "update_profile_handler";
+
+    let profile_data = json_validate(profile_body)?;
+    let log_id = profile_data.request_id.to_string();
+
+    let profile = UserServices::update_profile(
+        &log_id,
+        &user,
+        profile_data,
+        app_state.mail.verification_token_lifetime,
+        &app_state.db,
+        &app_state.rabbit
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "update_profile.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"update profile success",
+        "data":profile
+    })))
+}
+
+#[post("/password")]
+async fn change_password_handler(
+    req: HttpRequest,
+    password_body: Json<ChangePasswordData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "change_password_handler";
+
+    let password_data = json_validate(password_body)?;
+    let log_id = password_data.request_id.to_string();
+    let claims = access_claims(&req)?;
+
+    let payload = UserServices::change_password(
+        &log_id,
+        &claims,
+        password_data,
+        &session_metadata(&req),
+        &app_state.db,
+        &app_state.redis,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "change_password.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"password changed, other sessions were signed out",
+        "data":payload
+    })))
+}
+
+#[post("/2fa/setup")]
+async fn setup_totp_handler(
+    user: AuthUser,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "setup_totp_handler";
+    let log_id = format!("{} User.Setup_2fa",chrono::Utc::now());
+
+    let payload = UserServices::setup_totp(&log_id, &user, &app_state.mfa, &app_state.secret_cipher, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "setup_totp.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"scan the otpauth uri and confirm with a code",
+        "data":payload
+    })))
+}
+
+#[post("/2fa/confirm")]
+async fn confirm_totp_handler(
+    user: AuthUser,
+    confirm_body: Json<TotpConfirmData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "confirm_totp_handler";
+    let confirm_data = confirm_body.into_inner();
+    let log_id = confirm_data.request_id.to_string();
+
+    let recovery_codes = UserServices::confirm_totp(&log_id, &user, &confirm_data.code, &app_state.mfa, &app_state.secret_cipher, &app_state.db).await
+        .map_err(|error| log_failure(handler_name, &log_id, "confirm_totp.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"two-factor authentication enabled",
+        "data":{
+            "recovery_codes":recovery_codes
         }
-    }    
+    })))
+}
+
+#[delete("/account")]
+async fn delete_account_handler(
+    req: HttpRequest,
+    delete_body: Json<DeleteAccountData>,
+    app_state: Data<AppState>
+)-> Result<HttpResponse, AuthError>{
+    let handler_name = "delete_account_handler";
+    let delete_data = delete_body.into_inner();
+    let log_id = delete_data.request_id.to_string();
+    let claims = access_claims(&req)?;
+
+    UserServices::delete_account(
+        &log_id,
+        &claims,
+        delete_data,
+        &app_state.db,
+        &app_state.redis,
+        &app_state.rabbit,
+        &app_state.jwt
+    ).await.map_err(|error| log_failure(handler_name, &log_id, "delete_account.failed", error))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status":"success",
+        "message":"account deleted"
+    })))
+}
+
+pub fn jwks_config(config:&mut ServiceConfig){
+    config.service(jwks_handler);
 }
 
 pub fn auth_config(config:&mut ServiceConfig){
@@ -209,6 +522,14 @@ pub fn auth_config(config:&mut ServiceConfig){
         scope("/auth")
         .service(register_handlers)
         .service(login_handlers)
+        .service(verify_mfa_handler)
+        .service(verify_email_handler)
+        .service(forgot_password_handler)
+        .service(reset_password_handler)
+        .service(logout_handler)
+        .service(logout_all_handler)
+        .service(oauth_start_handler)
+        .service(oauth_callback_handler)
     );
 }
 pub fn token_config(config:&mut ServiceConfig){
@@ -224,5 +545,45 @@ pub fn user_config(config:&mut ServiceConfig){
         scope("/user")
         .wrap(AccessTokenMW)
         .service(user_profile_handler)
+        .service(revoke_token_handler)
+        .service(list_sessions_handler)
+        .service(revoke_session_handler)
+        .service(update_profile_handler)
+        .service(change_password_handler)
+        .service(delete_account_handler)
+        .service(setup_totp_handler)
+        .service(confirm_totp_handler)
     );
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug,Serialize,Clone,Validate)]
+    struct SignupData{
+        #[validate(length(min=5, message="too short"), contains(pattern="@", message="missing @"))]
+        email: String
+    }
+
+    #[test]
+    fn json_validate_keeps_every_field_error() {
+        let error = json_validate(Json(SignupData { email: String::from("ab") })).unwrap_err();
+        let AuthError::Validation { fields, .. } = error else { panic!("expected a validation error") };
+
+        let mut messages = fields["email"].clone();
+        messages.sort();
+        assert_eq!(messages, vec![String::from("missing @"), String::from("too short")]);
+    }
+
+    #[test]
+    fn session_metadata_ignores_forwarded_headers() {
+        let req = actix_web::test::TestRequest::default()
+            .insert_header(("x-forwarded-for", "203.0.113.7"))
+            .insert_header(("forwarded", "for=203.0.113.8"))
+            .peer_addr("10.0.0.2:51234".parse().unwrap())
+            .to_http_request();
+
+        assert_eq!(session_metadata(&req).ip.as_deref(), Some("10.0.0.2"));
+    }
+}
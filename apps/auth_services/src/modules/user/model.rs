@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Serialize,Deserialize};
 use uuid::Uuid;
 use validator::Validate;
@@ -20,7 +21,7 @@ pub struct RegisterPayload{
     pub id:Uuid,
     pub email: String,
     pub username: String,
-    pub phonenumber: String
+    pub phonenumber: Option<String>
 }
 
 #[derive(Debug,Deserialize,Serialize,Clone)]
@@ -36,7 +37,12 @@ pub struct LoginQueryPayload{
     pub id: Uuid,
     pub email: String,
     pub username: String,
-    pub password: String
+    pub password: Option<String>,
+    pub email_verified: bool,
+    pub totp_enabled: bool,
+    pub disabled: bool,
+    pub roles: Vec<String>,
+    pub permissions: Vec<String>
 }
 
 #[derive(Debug,Deserialize,Serialize)]
@@ -46,4 +52,141 @@ pub struct LoginPayload{
     pub username: String,
     pub refresh_token: String,
     pub access_token: String
-}
\ No newline at end of file
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct MfaPendingPayload{
+    pub mfa_token: String,
+    pub expires_in: i64
+}
+
+#[derive(Debug)]
+pub enum LoginResult{
+    Authenticated(LoginPayload),
+    MfaRequired(MfaPendingPayload)
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct RefreshTokenRow{
+    pub id: Uuid,
+    pub family_id: Uuid,
+    pub used_at: Option<DateTime<Utc>>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct RefreshTokenPayload{
+    pub refresh_token: String,
+    pub access_token: String
+}
+
+#[derive(Debug,Deserialize,Serialize,Clone,Default)]
+pub struct SessionMetadata{
+    pub user_agent: Option<String>,
+    pub ip: Option<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct SessionPayload{
+    pub id: Uuid,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct RegisterMessage{
+    pub user_id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub verification_token: String
+}
+
+#[derive(Debug,Deserialize)]
+pub struct VerifyEmailQuery{
+    pub token: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct ForgotPasswordData{
+    pub request_id: String,
+    #[validate(email(message="invalid format"))]
+    pub email: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct ResetPasswordData{
+    pub request_id: String,
+    pub token: String,
+    #[validate(length(min=5, message="too short"))]
+    pub password: String
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct PasswordResetMessage{
+    pub user_id: Uuid,
+    pub email: String,
+    pub username: String,
+    pub reset_token: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct UpdateProfileData{
+    pub request_id: String,
+    #[validate(email(message="invalid format"))]
+    pub email: Option<String>,
+    #[validate(length(min=5, message="too short"))]
+    pub phone_number: Option<String>,
+    #[validate(length(min=5, message="too short"))]
+    pub username: Option<String>
+}
+
+#[derive(Debug,Serialize,Deserialize,Validate,Clone)]
+pub struct ChangePasswordData{
+    pub request_id: String,
+    pub current_password: String,
+    #[validate(length(min=5, message="too short"))]
+    pub new_password: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone)]
+pub struct DeleteAccountData{
+    pub request_id: String,
+    pub password: String
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct UserDeletedEvent{
+    pub user_id: Uuid,
+    pub deleted_at: DateTime<Utc>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct TotpRow{
+    pub totp_enabled: bool,
+    pub totp_secret: Option<String>,
+    pub totp_pending_secret: Option<String>,
+    pub totp_last_counter: Option<i64>,
+    pub totp_recovery_codes: Vec<String>
+}
+
+#[derive(Debug,Deserialize,Serialize)]
+pub struct TotpSetupPayload{
+    pub secret: String,
+    pub otpauth_uri: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone)]
+pub struct TotpConfirmData{
+    pub request_id: String,
+    pub code: String
+}
+
+#[derive(Debug,Serialize,Deserialize,Clone)]
+pub struct MfaVerifyData{
+    pub request_id: String,
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>
+}
@@ -1,10 +1,17 @@
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
 use sqlx::{query, query_as, PgPool};
 use uuid::Uuid;
 
 use jwt_libs::types::AccessToken;
 
-use super::model::{LoginQueryPayload, RegisterData, RegisterPayload};
+use crate::error::AuthError;
 
+use super::model::{LoginQueryPayload, RefreshTokenRow, RegisterData, RegisterPayload, SessionMetadata, SessionPayload, TotpRow, UpdateProfileData};
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
 
 pub struct UserQuery {}
 
@@ -12,7 +19,7 @@ impl UserQuery {
     pub async fn create_user(
         data: RegisterData,
         db_pool: &PgPool
-    ) -> Result<RegisterPayload, String> {
+    ) -> Result<RegisterPayload, AuthError> {
         let existing_user = query_as!(
             RegisterPayload,
             r#"
@@ -25,20 +32,20 @@ impl UserQuery {
         )
         .fetch_optional(db_pool)
         .await
-        .map_err(|err| format!("Database error: {}", err))?;
+        ?;
 
-        if existing_user.is_some() {
+        if let Some(existing_user) = existing_user {
             let mut existing_value:Vec<String> = Vec::new();
-            if existing_user.as_ref().unwrap().email == data.email {
+            if existing_user.email == data.email {
                 existing_value.push("email".to_string());
             } 
-            if existing_user.as_ref().unwrap().username == data.username {
+            if existing_user.username == data.username {
                 existing_value.push("username".to_string());
             }
-            if existing_user.as_ref().unwrap().phonenumber == data.phone_number {
+            if existing_user.phonenumber.as_deref() == Some(data.phone_number.as_str()) {
                 existing_value.push("phone_number".to_string());
             }
-            return Err(format!("{} already exists", existing_value.join(", ")));
+            return Err(AuthError::Conflict(format!("{} already exists", existing_value.join(", "))));
         }
 
         let new_user = query_as!(
@@ -59,68 +66,540 @@ impl UserQuery {
         .fetch_one(db_pool)
         .await;
 
-        match new_user {
-            Ok(user) => Ok(user),
-            Err(err) => Err(format!("Database error: {}", err)),
+        new_user.map_err(AuthError::from)
+    }
+
+    pub async fn update_profile(
+        id: Uuid,
+        data: &UpdateProfileData,
+        db_pool: &PgPool
+    ) -> Result<(RegisterPayload, bool), AuthError> {
+        let mut tx = db_pool.begin().await?;
+
+        let current_user = query_as!(
+            RegisterPayload,
+            r#"SELECT id, email, username, phonenumber FROM "user" WHERE id = $1 FOR UPDATE"#,
+            id
+        )
+        .fetch_one(&mut *tx)
+        .await
+        ?;
+
+        let existing_users = query_as!(
+            RegisterPayload,
+            r#"
+                SELECT id, email, username, phonenumber FROM "user"
+                WHERE id <> $1 AND (email = $2 OR username = $3 OR phonenumber = $4);
+            "#,
+            id,
+            data.email,
+            data.username,
+            data.phone_number
+        )
+        .fetch_all(&mut *tx)
+        .await
+        ?;
+
+        if !existing_users.is_empty() {
+            let mut existing_value:Vec<String> = Vec::new();
+            if existing_users.iter().any(|user| Some(&user.email) == data.email.as_ref()) {
+                existing_value.push("email".to_string());
+            }
+            if existing_users.iter().any(|user| Some(&user.username) == data.username.as_ref()) {
+                existing_value.push("username".to_string());
+            }
+            if data.phone_number.is_some() && existing_users.iter().any(|user| user.phonenumber == data.phone_number) {
+                existing_value.push("phone_number".to_string());
+            }
+            return Err(AuthError::Conflict(format!("{} already exists", existing_value.join(", "))));
+        }
+
+        let email_changed = data.email.as_ref().is_some_and(|email| *email != current_user.email);
+
+        let updated_user = query_as!(
+            RegisterPayload,
+            r#"
+            UPDATE "user" SET
+                email = COALESCE($2, email),
+                username = COALESCE($3, username),
+                phonenumber = COALESCE($4, phonenumber),
+                email_verified = email_verified AND NOT $5
+            WHERE id = $1
+            RETURNING id, email, username, phonenumber
+            "#,
+            id,
+            data.email,
+            data.username,
+            data.phone_number,
+            email_changed
+        )
+        .fetch_one(&mut *tx)
+        .await
+        ?;
+
+        if updated_user.username != current_user.username {
+            query!(
+                r#"UPDATE "posts" SET username = $2 WHERE user_id = $1"#,
+                id,
+                updated_user.username
+            )
+            .execute(&mut *tx)
+            .await
+            ?;
+        }
+
+        tx.commit().await?;
+
+        Ok((updated_user, email_changed))
+    }
+
+    pub async fn find_password_by_id(
+        id:Uuid,
+        db_pool: &PgPool
+    )->Result<Option<String>, AuthError>{
+        match query!(
+            r#"
+                SELECT password FROM "user" WHERE id = $1
+            "#,
+            id
+        ).fetch_one(db_pool).await{
+            Ok(user)=>Ok(user.password),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn update_password(
+        id:Uuid,
+        password_hash:&str,
+        db_pool: &PgPool
+    )->Result<(), AuthError>{
+        match query!(
+            r#"
+                UPDATE "user" SET password = $2 WHERE id = $1
+            "#,
+            id,
+            password_hash
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn delete_user(
+        id:Uuid,
+        db_pool: &PgPool
+    )->Result<(), AuthError>{
+        match query!(
+            r#"
+                DELETE FROM "user" WHERE id = $1
+            "#,
+            id
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn create_email_verification_token(
+        token:&str,
+        user_id:Uuid,
+        expires_at:DateTime<Utc>,
+        db_pool: &PgPool
+    )->Result<(), AuthError>{
+        match query!(
+            r#"
+            INSERT INTO "email_verification_token"
+            (user_id,token_hash,expires_at) VALUES
+            ($1,$2,$3)
+            "#,
+            user_id,
+            hash_token(token),
+            expires_at
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn verify_email(
+        token:&str,
+        db_pool: &PgPool
+    )->Result<Uuid, AuthError>{
+        match query!(
+            r#"
+                WITH consumed AS (
+                    DELETE FROM "email_verification_token"
+                    WHERE token_hash = $1 AND expires_at > now()
+                    RETURNING user_id
+                )
+                UPDATE "user" SET email_verified = TRUE
+                FROM consumed WHERE "user".id = consumed.user_id
+                RETURNING "user".id
+            "#,
+            hash_token(token)
+        ).fetch_optional(db_pool).await{
+            Ok(Some(user))=>Ok(user.id),
+            Ok(None)=>Err(AuthError::validation("invalid or expired verification token")),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_user_by_email(
+        email:&str,
+        db_pool: &PgPool
+    )->Result<Option<AccessToken>, AuthError>{
+        match query_as!(
+            AccessToken,
+            r#"
+                SELECT id, username, email,
+                ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = "user".id ORDER BY r.name) AS "roles!",
+                ARRAY(SELECT DISTINCT p.name FROM "user_role" ur JOIN "role_permission" rp ON rp.role_id = ur.role_id JOIN "permission" p ON p.id = rp.permission_id WHERE ur.user_id = "user".id ORDER BY p.name) AS "permissions!"
+                FROM "user" WHERE email = $1
+            "#,
+            email
+        ).fetch_optional(db_pool).await{
+            Ok(user)=>Ok(user),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn create_password_reset_token(
+        token:&str,
+        user_id:Uuid,
+        expires_at:DateTime<Utc>,
+        db_pool: &PgPool
+    )->Result<(), AuthError>{
+        match query!(
+            r#"
+            INSERT INTO "password_reset_token"
+            (user_id,token_hash,expires_at) VALUES
+            ($1,$2,$3)
+            "#,
+            user_id,
+            hash_token(token),
+            expires_at
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn reset_password(
+        token:&str,
+        password_hash:&str,
+        db_pool: &PgPool
+    )->Result<Uuid, AuthError>{
+        match query!(
+            r#"
+                WITH consumed AS (
+                    DELETE FROM "password_reset_token"
+                    WHERE token_hash = $1 AND expires_at > now()
+                    RETURNING user_id
+                ), updated AS (
+                    UPDATE "user" SET password = $2
+                    FROM consumed WHERE "user".id = consumed.user_id
+                    RETURNING "user".id
+                ), cleared AS (
+                    DELETE FROM "password_reset_token"
+                    WHERE user_id IN (SELECT id FROM updated) AND token_hash <> $1
+                )
+                SELECT id FROM updated
+            "#,
+            hash_token(token),
+            password_hash
+        ).fetch_optional(db_pool).await{
+            Ok(Some(user))=>Ok(user.id),
+            Ok(None)=>Err(AuthError::validation("invalid or expired reset token")),
+            Err(error)=>Err(error.into())
         }
     }
 
     pub async fn create_refresh_token(
         token:&str,
         userid:Uuid,
+        family_id:Uuid,
+        expires_at:DateTime<Utc>,
+        metadata:&SessionMetadata,
         db_pool: &PgPool
-    )->Result<(),String>{
-        let _ = query!(
+    )->Result<(), AuthError>{
+        match query!(
             r#"
             INSERT INTO "refresh_token" 
-            (userid,refreshtoken) VALUES
-            ($1,$2)
+            (userid,token_hash,family_id,expires_at,user_agent,ip) VALUES
+            ($1,$2,$3,$4,$5,$6)
             "#,
             userid,
-            token
-        ).execute(db_pool).await.map_err(|error|{
-            format!("error database: {}",error)
-        });
-        Ok(())
+            hash_token(token),
+            family_id,
+            expires_at,
+            metadata.user_agent,
+            metadata.ip
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
     }
     
     pub async fn find_refresh_token(
-        token:String,
+        token:&str,
         userid:Uuid,
         db_pool: &PgPool
-    )-> Result<Uuid,String>{
-        match query!(
+    )-> Result<RefreshTokenRow, AuthError>{
+        match query_as!(
+            RefreshTokenRow,
             r#"
-                SELECT * FROM "refresh_token" where userid = $1 AND refreshtoken = $2
+                SELECT id, family_id, used_at FROM "refresh_token"
+                WHERE userid = $1 AND token_hash = $2 AND expires_at > now() AND client_id IS NULL
             "#,
             userid,
-            token
+            hash_token(token)
+        ).fetch_optional(db_pool).await{
+            Ok(Some(row))=>Ok(row),
+            Ok(None)=>Err(AuthError::Unauthorized(String::from("invalid token: refresh token not found"))),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn consume_refresh_token(
+        id:Uuid,
+        db_pool: &PgPool
+    )-> Result<bool, AuthError>{
+        match query!(
+            r#"
+                UPDATE "refresh_token" SET used_at = now() WHERE id = $1 AND used_at IS NULL
+            "#,
+            id
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected() == 1),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn delete_refresh_token_family(
+        family_id:Uuid,
+        db_pool: &PgPool
+    )-> Result<u64, AuthError>{
+        match query!(
+            r#"
+                DELETE FROM "refresh_token" WHERE family_id = $1
+            "#,
+            family_id
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn delete_user_refresh_tokens(
+        userid:Uuid,
+        db_pool: &PgPool
+    )-> Result<u64, AuthError>{
+        match query!(
+            r#"
+                DELETE FROM "refresh_token" WHERE userid = $1
+            "#,
+            userid
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_user_sessions(
+        userid:Uuid,
+        db_pool: &PgPool
+    )-> Result<Vec<SessionPayload>, AuthError>{
+        match query_as!(
+            SessionPayload,
+            r#"
+                SELECT
+                    token.family_id AS id,
+                    token.user_agent,
+                    token.ip,
+                    (SELECT MIN(family.created_at) FROM "refresh_token" family WHERE family.family_id = token.family_id) AS "created_at!",
+                    token.created_at AS last_used_at,
+                    token.expires_at
+                FROM "refresh_token" token
+                WHERE token.userid = $1 AND token.used_at IS NULL AND token.expires_at > now()
+                ORDER BY token.created_at DESC
+            "#,
+            userid
+        ).fetch_all(db_pool).await{
+            Ok(sessions)=>Ok(sessions),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn delete_user_session(
+        family_id:Uuid,
+        userid:Uuid,
+        db_pool: &PgPool
+    )-> Result<u64, AuthError>{
+        match query!(
+            r#"
+                DELETE FROM "refresh_token" WHERE family_id = $1 AND userid = $2
+            "#,
+            family_id,
+            userid
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn purge_expired_refresh_tokens(
+        db_pool: &PgPool
+    )-> Result<u64, AuthError>{
+        match query!(
+            r#"
+                DELETE FROM "refresh_token" WHERE expires_at <= now()
+            "#
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn record_login_failure(
+        identifier:&str,
+        reason:&str,
+        metadata:&SessionMetadata,
+        db_pool: &PgPool
+    )->Result<(), AuthError>{
+        match query!(
+            r#"
+                INSERT INTO "login_failure" (user_id, identifier, reason, ip, user_agent)
+                VALUES ((SELECT id FROM "user" WHERE email = $1 OR username = $1 LIMIT 1), $1, $2, $3, $4)
+            "#,
+            identifier,
+            reason,
+            metadata.ip,
+            metadata.user_agent
+        ).execute(db_pool).await{
+            Ok(_)=>Ok(()),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn find_totp(
+        user_id:Uuid,
+        db_pool: &PgPool
+    )->Result<TotpRow, AuthError>{
+        match query_as!(
+            TotpRow,
+            r#"
+                SELECT totp_enabled, totp_secret, totp_pending_secret, totp_last_counter, totp_recovery_codes
+                FROM "user" WHERE id = $1
+            "#,
+            user_id
         ).fetch_one(db_pool).await{
-            Ok(_)=>Ok(userid),
-            Err(error)=>{
-                if format!("{}",error).contains("no rows returned"){
-                    return Err("refresh token not found".to_string());
-                }
-                Err(format!("error database: {}", error))
-            }
+            Ok(row)=>Ok(row),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    // Returns false when two-factor authentication is already enabled for the user.
+    pub async fn set_totp_pending_secret(
+        user_id:Uuid,
+        encrypted_secret:&str,
+        db_pool: &PgPool
+    )->Result<bool, AuthError>{
+        match query!(
+            r#"
+                UPDATE "user" SET totp_pending_secret = $2 WHERE id = $1 AND NOT totp_enabled
+            "#,
+            user_id,
+            encrypted_secret
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected() > 0),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    // Only promotes the pending secret the code was checked against, so a concurrent setup cannot swap it.
+    pub async fn enable_totp(
+        user_id:Uuid,
+        pending_secret:&str,
+        counter:i64,
+        recovery_code_hashes:&[String],
+        db_pool: &PgPool
+    )->Result<bool, AuthError>{
+        match query!(
+            r#"
+                UPDATE "user"
+                SET totp_secret = totp_pending_secret,
+                    totp_pending_secret = NULL,
+                    totp_enabled = TRUE,
+                    totp_last_counter = $3,
+                    totp_recovery_codes = $4
+                WHERE id = $1 AND totp_pending_secret = $2 AND NOT totp_enabled
+            "#,
+            user_id,
+            pending_secret,
+            counter,
+            recovery_code_hashes
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected() > 0),
+            Err(error)=>Err(error.into())
         }
+    }
 
+    // Returns false when the time step was already used, which makes every code single-use.
+    pub async fn record_totp_counter(
+        user_id:Uuid,
+        counter:i64,
+        db_pool: &PgPool
+    )->Result<bool, AuthError>{
+        match query!(
+            r#"
+                UPDATE "user" SET totp_last_counter = $2
+                WHERE id = $1 AND (totp_last_counter IS NULL OR totp_last_counter < $2)
+            "#,
+            user_id,
+            counter
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected() > 0),
+            Err(error)=>Err(error.into())
+        }
+    }
+
+    pub async fn consume_recovery_code(
+        user_id:Uuid,
+        code_hash:&str,
+        db_pool: &PgPool
+    )->Result<bool, AuthError>{
+        match query!(
+            r#"
+                UPDATE "user" SET totp_recovery_codes = array_remove(totp_recovery_codes, $2)
+                WHERE id = $1 AND $2 = ANY(totp_recovery_codes)
+            "#,
+            user_id,
+            code_hash
+        ).execute(db_pool).await{
+            Ok(result)=>Ok(result.rows_affected() > 0),
+            Err(error)=>Err(error.into())
+        }
     }
 
     pub async fn login_query(
         email: Option<String>,
         username: Option<String>,
         db_pool: &PgPool
-    )->Result<LoginQueryPayload,String>{
+    )->Result<LoginQueryPayload, AuthError>{
         
         if email.is_none() && username.is_none(){
-            return Err(String::from("email and username is empty!"));
+            return Err(AuthError::validation("email or username is required"));
         }
         
         let login_payload = if let Some(email) = email {
             query_as!(
                 LoginQueryPayload,
-                r#"SELECT id, email, username, password FROM "user" WHERE email = $1"#,
+                r#"
+                SELECT id, email, username, password, email_verified, totp_enabled, disabled_at IS NOT NULL AS "disabled!",
+                ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = "user".id ORDER BY r.name) AS "roles!",
+                ARRAY(SELECT DISTINCT p.name FROM "user_role" ur JOIN "role_permission" rp ON rp.role_id = ur.role_id JOIN "permission" p ON p.id = rp.permission_id WHERE ur.user_id = "user".id ORDER BY p.name) AS "permissions!"
+                FROM "user" WHERE email = $1
+                "#,
                 email
             )
             .fetch_one(db_pool)
@@ -128,31 +607,51 @@ impl UserQuery {
         } else {
             query_as!(
                 LoginQueryPayload,
-                r#"SELECT id, email, username, password FROM "user" WHERE username = $1"#,
+                r#"
+                SELECT id, email, username, password, email_verified, totp_enabled, disabled_at IS NOT NULL AS "disabled!",
+                ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = "user".id ORDER BY r.name) AS "roles!",
+                ARRAY(SELECT DISTINCT p.name FROM "user_role" ur JOIN "role_permission" rp ON rp.role_id = ur.role_id JOIN "permission" p ON p.id = rp.permission_id WHERE ur.user_id = "user".id ORDER BY p.name) AS "permissions!"
+                FROM "user" WHERE username = $1
+                "#,
                 username
             )
             .fetch_one(db_pool)
             .await
         };
-        match login_payload {
-            Ok(payload)=> Ok(payload),
-            Err(message)=>Err(format!("{}",message))
+        login_payload.map_err(AuthError::from)
+    }
+
+    pub async fn is_user_disabled(
+        id: Uuid,
+        db_pool: &PgPool
+    )-> Result<bool, AuthError>{
+        match query!(
+            r#"
+            SELECT disabled_at IS NOT NULL AS "disabled!" FROM "user" WHERE id = $1
+            "#,
+            id
+        ).fetch_one(db_pool).await{
+            Ok(user)=>Ok(user.disabled),
+            Err(error)=>Err(error.into())
         }
     }
 
     pub async fn find_user_by_id(
         id: Uuid,
         db_pool: &PgPool
-    )-> Result<AccessToken,String>{
+    )-> Result<AccessToken, AuthError>{
         match query_as!(
             AccessToken,
             r#"
-            SELECT id, username, email FROM "user" where id = $1;
+            SELECT id, username, email,
+            ARRAY(SELECT r.name FROM "user_role" ur JOIN "role" r ON r.id = ur.role_id WHERE ur.user_id = "user".id ORDER BY r.name) AS "roles!",
+            ARRAY(SELECT DISTINCT p.name FROM "user_role" ur JOIN "role_permission" rp ON rp.role_id = ur.role_id JOIN "permission" p ON p.id = rp.permission_id WHERE ur.user_id = "user".id ORDER BY p.name) AS "permissions!"
+            FROM "user" where id = $1;
             "#,
             id
         ).fetch_one(db_pool).await{
             Ok(user)=>Ok(user),
-            Err(error)=> Err(format!("db error: {}", error))
+            Err(error)=>Err(error.into())
         }
     }
 }
\ No newline at end of file
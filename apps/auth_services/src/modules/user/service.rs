@@ -1,26 +1,81 @@
-use argon2::{password_hash::{rand_core::OsRng, SaltString}, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
-use lapin::{options::BasicPublishOptions, publisher_confirm::PublisherConfirm, types::FieldTable, BasicProperties};
+use std::time::Duration;
+
+use argon2::{password_hash::{rand_core::{OsRng, RngCore}, SaltString}, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use chrono::{DateTime, Utc};
+use lapin::{options::{BasicPublishOptions, ConfirmSelectOptions, ExchangeDeclareOptions}, publisher_confirm::Confirmation, types::FieldTable, BasicProperties, ExchangeKind};
 use log::info;
 use logger_libs::Logger;
 use pgsql_libs::DbPool;
-use r2d2_redis::redis::{Commands, RedisError};
 use rabbitmq_libs::RabbitMqPool;
-use redis_libs::{create_redis_connection, RedisPool};
+use redis_libs::{exempt_token, is_token_revoked, rate_limit::{delete_key, increment_counter, lock_remaining, set_lock, sliding_window_hit, RateLimitDecision}, revoke_subject_tokens, revoke_token, RedisPool};
+use serde::Serialize;
 use serde_json::json;
+use uuid::Uuid;
+
+use jwt_libs::{error::JwtLibError, {decode_access_token, decode_mfa_pending_token, decode_refresh_token, generate_access_token, generate_mfa_pending_token, generate_refresh_token},keys::JwtKeyring,types::{AccessToken, MfaPendingToken, RefreshToken, TokenClaims}};
 
-use jwt_libs::{{decode_refresh_token, generate_access_token, generate_refresh_token},types::{AccessToken, RefreshToken}};
+use crate::{config_type::{LoginLimit, Mfa}, error::AuthError, modules::{mail::consumer::{PASSWORD_RESET_QUEUE, REGISTER_QUEUE}, mfa::totp::{encode_secret, generate_recovery_codes, generate_secret, normalize_recovery_code, otpauth_uri, verify_code, SecretCipher}}};
 
-use super::{model::{LoginData, LoginPayload, RegisterData, RegisterPayload}, query::UserQuery};
+use super::{model::{ChangePasswordData, DeleteAccountData, ForgotPasswordData, LoginData, LoginPayload, LoginResult, MfaPendingPayload, MfaVerifyData, PasswordResetMessage, RefreshTokenPayload, RegisterData, RegisterMessage, RegisterPayload, ResetPasswordData, SessionMetadata, SessionPayload, TotpSetupPayload, UpdateProfileData, UserDeletedEvent}, query::{hash_token, UserQuery}};
 
 pub struct UserServices{}
 
+pub const USER_EVENTS_EXCHANGE: &str = "user_events";
+
+const LOGIN_LIMIT_PREFIX: &str = "login_limit";
+const LOGIN_FAILURES_PREFIX: &str = "login_failures";
+const LOGIN_LOCK_PREFIX: &str = "login_lock";
+const MFA_ATTEMPTS_PREFIX: &str = "mfa_attempts";
+
+fn hash_password(password: &str) -> Result<String, AuthError> {
+    let salt: SaltString = SaltString::generate(&mut OsRng);
+    match Argon2::default().hash_password(password.as_bytes(), &salt) {
+        Ok(hash) => Ok(hash.to_string()),
+        Err(e) => Err(AuthError::Internal(format!("password hash error: {}", e))),
+    }
+}
+
+fn generate_secret_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn login_identifier(data: &LoginData) -> String {
+    data.email.as_deref().or(data.username.as_deref()).unwrap_or_default().trim().to_lowercase()
+}
+
+// Only credential failures count towards the lockout; anything else is not the caller's guess.
+pub fn login_failure_reason(error: &AuthError) -> Option<&'static str> {
+    match error {
+        AuthError::InvalidCredentials => Some("invalid_credentials"),
+        _ => None,
+    }
+}
+
+fn account_locked(retry_after: i64) -> AuthError {
+    AuthError::RateLimited { message: String::from("too many failed login attempts, account temporarily locked"), retry_after }
+}
+
+pub fn token_error(error: JwtLibError) -> AuthError {
+    match error.is_unauthorized() {
+        true => AuthError::Unauthorized(format!("invalid token: {}", error)),
+        false => AuthError::Internal(format!("error decode token: {}", error)),
+    }
+}
+
+fn refresh_token_expiry(jwt_keyring: &JwtKeyring) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(jwt_keyring.lifetimes().refresh_token)
+}
+
 impl UserServices {
     pub async fn login(
         log_id: &str,
         data: LoginData,
+        metadata: &SessionMetadata,
         db_pool: &DbPool,
-        redis_pool: &RedisPool
-    ) -> Result<LoginPayload, String> {
+        jwt_keyring: &JwtKeyring
+    ) -> Result<LoginResult, AuthError> {
         let handler_name = "login_service";
         let login_data: super::model::LoginQueryPayload = match UserQuery::login_query(data.email.clone(), data.username.clone(), db_pool).await {
             Ok(login_data) => {
@@ -28,75 +83,133 @@ impl UserServices {
                 login_data
             },
             Err(error) => {
-                Logger::warning_logger(handler_name, log_id, "login_services.data_validate", &error);
-                return Err(format!("Database error: {}", error))
+                Logger::warning_logger(handler_name, log_id, "login_services.data_validate", &error.to_string());
+                return Err(match error {
+                    AuthError::NotFound(_) => AuthError::InvalidCredentials,
+                    error => error,
+                })
             }
         };
     
         let argon2 = Argon2::default();
     
-        let parsed_hash = match PasswordHash::new(&login_data.password) {
+        // Accounts created through a social login have no password until one is set with a reset.
+        let Some(stored_password) = login_data.password.as_deref() else {
+            Logger::warning_logger(handler_name, log_id, "login_service.password_validate", "password not set");
+            return Err(AuthError::InvalidCredentials);
+        };
+
+        let parsed_hash = match PasswordHash::new(stored_password) {
             Ok(parsed_hash) => {
-                Logger::info_logger(&handler_name, log_id, "login_service.password_validate");
+                Logger::info_logger(handler_name, log_id, "login_service.password_validate");
                 parsed_hash
             },
             Err(err) => {
-                Logger::warning_logger(&handler_name, log_id, "login_service.password_validate", &format!("{}",err));
-                return Err("Error parsing stored password hash".to_string())
+                Logger::warning_logger(handler_name, log_id, "login_service.password_validate", &format!("{}",err));
+                return Err(AuthError::Internal(String::from("error parsing stored password hash")))
             },
         };
     
         if let Err(err) = argon2.verify_password(data.password.as_bytes(), &parsed_hash) {
-            Logger::warning_logger(&handler_name, log_id, "login_service.password_validate", &format!("{}",err));
-            return Err(format!("Invalid password: {}", err));
+            Logger::warning_logger(handler_name, log_id, "login_service.password_validate", &format!("{}",err));
+            return Err(AuthError::InvalidCredentials);
         }
 
-        let refresh_token_data = RefreshToken {
+        if login_data.disabled {
+            let error = AuthError::Forbidden(String::from("account disabled"));
+            Logger::warning_logger(handler_name, log_id, "login_service.account_disabled", &error.to_string());
+            return Err(error);
+        }
+
+        if !login_data.email_verified {
+            let error = AuthError::Forbidden(String::from("email not verified"));
+            Logger::warning_logger(handler_name, log_id, "login_service.email_verified", &error.to_string());
+            return Err(error);
+        }
+
+        let user = AccessToken {
             id: login_data.id,
+            email: login_data.email,
+            username: login_data.username,
+            roles: login_data.roles,
+            permissions: login_data.permissions,
+        };
+
+        Self::complete_login(log_id, user, login_data.totp_enabled, metadata, db_pool, jwt_keyring).await
+    }
+
+    // Shared by every first factor: accounts with TOTP get an MFA challenge instead of tokens.
+    pub async fn complete_login(
+        log_id: &str,
+        user: AccessToken,
+        totp_enabled: bool,
+        metadata: &SessionMetadata,
+        db_pool: &DbPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<LoginResult, AuthError> {
+        let handler_name = "complete_login_service";
+        if totp_enabled {
+            return match generate_mfa_pending_token(MfaPendingToken { id: user.id }, jwt_keyring) {
+                Ok(mfa_token) => {
+                    Logger::info_logger(handler_name, log_id, "login_service.generate_mfa_pending_token");
+                    Ok(LoginResult::MfaRequired(MfaPendingPayload { mfa_token, expires_in: jwt_keyring.lifetimes().mfa_pending }))
+                },
+                Err(error) => {
+                    Logger::warning_logger(handler_name, log_id, "login_service.generate_mfa_pending_token", &error.to_string());
+                    Err(AuthError::Internal(format!("error generating mfa token: {}", error)))
+                }
+            }
+        }
+
+        Self::issue_login_tokens(log_id, user, metadata, db_pool, jwt_keyring).await.map(LoginResult::Authenticated)
+    }
+
+    async fn issue_login_tokens(
+        log_id: &str,
+        user: AccessToken,
+        metadata: &SessionMetadata,
+        db_pool: &DbPool,
+        jwt_keyring: &JwtKeyring
+    ) -> Result<LoginPayload, AuthError> {
+        let handler_name = "issue_login_tokens";
+        let refresh_token_data = RefreshToken {
+            id: user.id,
         };
         
-        match generate_refresh_token(refresh_token_data) {
+        match generate_refresh_token(refresh_token_data, jwt_keyring) {
             Ok(refresh_token) => {
-                Logger::info_logger(handler_name, log_id, "login_services.generate_refresh_token");
-                match UserQuery::create_refresh_token(&refresh_token, login_data.id, db_pool).await {
+                Logger::info_logger(handler_name, log_id, "issue_login_tokens.generate_refresh_token");
+                match UserQuery::create_refresh_token(&refresh_token, user.id, Uuid::new_v4(), refresh_token_expiry(jwt_keyring), metadata, db_pool).await {
                     Ok(_) => {
-                        let access_token_data = AccessToken {
-                            id: login_data.id,
-                            email: login_data.email.clone(),
-                            username: login_data.username.clone(),
-                        };
-                        
-                        Logger::info_logger (handler_name, log_id, "login_service.save_refresh_token");
-
-                        match generate_access_token(access_token_data) {
+                        Logger::info_logger(handler_name, log_id, "issue_login_tokens.save_refresh_token");
+
+                        match generate_access_token(user.clone(), jwt_keyring) {
                             Ok(access_token) => {
                                 let payload = LoginPayload {
-                                    id: login_data.id,
-                                    email: login_data.email,
-                                    username: login_data.username,
+                                    id: user.id,
+                                    email: user.email,
+                                    username: user.username,
                                     access_token,
-                                    refresh_token: refresh_token.clone(),
+                                    refresh_token,
                                 };
 
-                                let _ = Self::refresh_token(log_id,refresh_token, db_pool, redis_pool);
-
                                 Ok(payload)
                             }
                             Err(error) => {
-                                Logger::warning_logger(handler_name, log_id, "login_service.generate_access_token",&error);
-                                Err(format!("Error generating access token: {}", error))
+                                Logger::warning_logger(handler_name, log_id, "issue_login_tokens.generate_access_token",&error.to_string());
+                                Err(AuthError::Internal(format!("error generating access token: {}", error)))
                             },
                         }
                     }
                     Err(error) => {
-                        Logger::warning_logger(handler_name, log_id, "login_services.save_refresh_token", &error);
-                        Err(format!("Error saving refresh token to database: {}", error))
+                        Logger::warning_logger(handler_name, log_id, "issue_login_tokens.save_refresh_token", &error.to_string());
+                        Err(error)
                     },
                 }
             }
             Err(error) => {
-                Logger::warning_logger(handler_name, log_id, "login_service.generate_refresh_token", &error);
-                Err(format!("Error generating refresh token: {}", error))
+                Logger::warning_logger(handler_name, log_id, "issue_login_tokens.generate_refresh_token", &error.to_string());
+                Err(AuthError::Internal(format!("error generating refresh token: {}", error)))
             },
         }
     }    
@@ -104,140 +217,740 @@ impl UserServices {
     pub async fn refresh_token(
         log_id: &str,
         token: String,
+        metadata: &SessionMetadata,
         db_pool: &DbPool,
-        redis_pool: &RedisPool
-    )->Result<String,String>{
+        jwt_keyring: &JwtKeyring
+    )->Result<RefreshTokenPayload, AuthError>{
         let handler_name = "refresh_token";
-        let decode_token = decode_refresh_token(&token).map_err(|err|{
-            if err.contains("InvalidSignature"){
-                let err_message = String::from("error input: invalid token");
-                Logger::warning_logger(handler_name, log_id, "refresh_token.decode_token", &err_message);
-                return err_message
-            }
-            let err_message=   format!("error decode token: {}",err);
-            Logger::warning_logger(handler_name, log_id, "refresh_token.decode_token", &err_message);
-
-            return err_message
+        let decode_token = decode_refresh_token(&token, jwt_keyring).map_err(|err|{
+            let error = token_error(err);
+            Logger::warning_logger(handler_name, log_id, "refresh_token.decode_token", &error.to_string());
+            error
         })?;
 
-        let user_id = UserQuery::find_refresh_token(token, decode_token.claims.token.id, db_pool).await.map_err(|err|{
-            let err_message= format!("error find refresh token: {}",err);
+        let user_id = decode_token.claims.token.id;
 
-            Logger::warning_logger(handler_name, log_id, "refresh_token.find_refresh_token", &err_message);
-            return err_message
-        })?;
+        // The disabled_sub Redis flag can be lost on a flush or failover; the user row is the source of truth.
+        if UserQuery::is_user_disabled(user_id, db_pool).await.inspect_err(|err|{
+            Logger::warning_logger(handler_name, log_id, "refresh_token.is_user_disabled", &err.to_string());
+        })? {
+            let error = AuthError::Forbidden(String::from("account disabled"));
+            Logger::warning_logger(handler_name, log_id, "refresh_token.account_disabled", &error.to_string());
+            return Err(error)
+        }
 
-        let user = UserQuery::find_user_by_id(user_id, db_pool).await.map_err(|err|{
-            let err_message= format!("error find user: {}",err); 
-           
-           Logger::warning_logger(handler_name, log_id, "refresh_token.validate_user_id", &err_message);
-           return err_message
+        let stored_token = UserQuery::find_refresh_token(&token, user_id, db_pool).await.inspect_err(|err|{
+            Logger::warning_logger(handler_name, log_id, "refresh_token.find_refresh_token", &err.to_string());
         })?;
 
-        let access_token = generate_access_token(user).map_err(|err|{
-            let err_message= format!("error generate access token: {}",err);
-            
-            Logger::warning_logger(handler_name, log_id, "refresh_token.generate_access_token", &err_message);
+        let consumed = match stored_token.used_at {
+            Some(_) => false,
+            None => UserQuery::consume_refresh_token(stored_token.id, db_pool).await.inspect_err(|err|{
+                Logger::warning_logger(handler_name, log_id, "refresh_token.consume_refresh_token", &err.to_string());
+            })?,
+        };
+
+        if !consumed {
+            let error = AuthError::Unauthorized(String::from("invalid token: refresh token reused, session revoked"));
+            Logger::warning_logger(handler_name, log_id, "refresh_token.reuse_detected", &error.to_string());
+
+            if let Err(err) = UserQuery::delete_refresh_token_family(stored_token.family_id, db_pool).await {
+                Logger::warning_logger(handler_name, log_id, "refresh_token.delete_refresh_token_family", &err.to_string());
+            }
+            return Err(error)
+        }
 
-            return err_message
+        let user = UserQuery::find_user_by_id(user_id, db_pool).await.inspect_err(|err|{
+            Logger::warning_logger(handler_name, log_id, "refresh_token.validate_user_id", &err.to_string());
         })?;
 
-        let _ = Self::delete_access_token(redis_pool);
+        let refresh_token = generate_refresh_token(RefreshToken { id: user_id }, jwt_keyring).map_err(|err|{
+            let error = AuthError::Internal(format!("error generate refresh token: {}",err));
+            Logger::warning_logger(handler_name, log_id, "refresh_token.generate_refresh_token", &error.to_string());
+            error
+        })?;
 
-        let _ = Self::store_access_token(access_token.clone(), redis_pool).map_err(|err|{
-            let err_message = format!("error store access token: {}",err);
+        UserQuery::create_refresh_token(&refresh_token, user_id, stored_token.family_id, refresh_token_expiry(jwt_keyring), metadata, db_pool).await.inspect_err(|err|{
+            Logger::warning_logger(handler_name, log_id, "refresh_token.save_refresh_token", &err.to_string());
+        })?;
 
-            Logger::warning_logger(&handler_name, log_id, "refresh_token.store_access_token", &err_message);
-            return err_message
+        let access_token = generate_access_token(user, jwt_keyring).map_err(|err|{
+            let error = AuthError::Internal(format!("error generate access token: {}",err));
+            Logger::warning_logger(handler_name, log_id, "refresh_token.generate_access_token", &error.to_string());
+            error
         })?;
 
-        Ok(access_token)
+        Ok(RefreshTokenPayload { refresh_token, access_token })
     }
 
-    pub fn store_access_token(
-        token: String,
-        redis_pool: &RedisPool,
-    ) -> Result<(), String> {
-        let redis_key = format!("access_token");
+    pub fn check_login_limit(
+        log_id: &str,
+        identifier: &str,
+        ip: Option<&str>,
+        login_limit: &LoginLimit,
+        redis_pool: &RedisPool
+    ) -> Result<(), AuthError> {
+        let handler_name = "check_login_limit";
 
-        let mut conn = redis_pool.get().map_err(|e| e.to_string())?;
+        if let Some(lockout) = lock_remaining(redis_pool, &format!("{}:{}", LOGIN_LOCK_PREFIX, identifier)).map_err(AuthError::Upstream)? {
+            Logger::warning_logger(handler_name, log_id, "check_login_limit.account_locked", identifier);
+            return Err(account_locked(lockout));
+        }
 
-        let set_data: Result<String, RedisError> = conn.set(&redis_key, token.clone());
+        let mut windows = vec![(format!("{}:account:{}", LOGIN_LIMIT_PREFIX, identifier), login_limit.account_attempts)];
+        if let Some(ip) = ip {
+            windows.push((format!("{}:ip:{}", LOGIN_LIMIT_PREFIX, ip), login_limit.ip_attempts));
+        }
 
-        let ttl = 12000;
-        let _ = conn.expire::<String,String>(redis_key, ttl);
-        match set_data {
-            Ok(data)=>{
-                info!("data inserted: {}",data);
-                Ok(())
-            },
-            Err(error)=>{
-                Err(format!("error redis: {}", error))
+        for (key, limit) in windows {
+            if let RateLimitDecision::Limited { retry_after } = sliding_window_hit(redis_pool, &key, limit, login_limit.window).map_err(AuthError::Upstream)? {
+                Logger::warning_logger(handler_name, log_id, "check_login_limit.rate_limited", &key);
+                return Err(AuthError::RateLimited { message: String::from("too many login attempts, try again later"), retry_after });
             }
         }
+
+        Ok(())
     }
 
-        pub fn delete_access_token(
-            redis_pool: &RedisPool
-        )-> (){
-            let redis_key = format!("access_token");
+    // Returns the lockout error when this failure locked the account.
+    pub async fn record_login_failure(
+        log_id: &str,
+        identifier: &str,
+        reason: &str,
+        metadata: &SessionMetadata,
+        login_limit: &LoginLimit,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool
+    ) -> Result<Option<AuthError>, AuthError> {
+        let handler_name = "record_login_failure";
 
-            let mut conn = create_redis_connection(redis_pool).expect("failed to connect redis");
+        if let Err(error) = UserQuery::record_login_failure(identifier, reason, metadata, db_pool).await {
+            Logger::err_logger(handler_name, log_id, "record_login_failure.audit", error.to_string());
+        }
 
-            let set_data: Result<String, RedisError> = conn.del(&redis_key);
-            match set_data {
-                Ok(data)=>{
-                    info!("data delete: {}",data);
-                },
-                Err(error)=>{
-                    if error.to_string() != "response was int(0)"{
-                        info!("key not found");
-                    }
-                    info!("{}",format!("error redis: {}", error))
-                }
-            }    
+        let failures_key = format!("{}:{}", LOGIN_FAILURES_PREFIX, identifier);
+        let failures = increment_counter(redis_pool, &failures_key, login_limit.failure_window).map_err(AuthError::Upstream)?;
+        if failures < login_limit.max_failures {
+            return Ok(None);
+        }
+
+        set_lock(redis_pool, &format!("{}:{}", LOGIN_LOCK_PREFIX, identifier), login_limit.lockout).map_err(AuthError::Upstream)?;
+        delete_key(redis_pool, &failures_key).map_err(AuthError::Upstream)?;
+        Logger::warning_logger(handler_name, log_id, "record_login_failure.account_locked", identifier);
+
+        Ok(Some(account_locked(login_limit.lockout)))
+    }
+
+    pub fn reset_login_failures(
+        identifier: &str,
+        redis_pool: &RedisPool
+    ) -> Result<(), AuthError> {
+        delete_key(redis_pool, &format!("{}:{}", LOGIN_FAILURES_PREFIX, identifier)).map_err(AuthError::Upstream)
     }
 
     pub async fn find_user_login(
         log_id: &str,
         token: AccessToken,
         db_pool: &DbPool
-    )-> Result<AccessToken,String>{
+    )-> Result<AccessToken, AuthError>{
         let handler_name = "find_user_services";
         match UserQuery::find_user_by_id(token.id, db_pool).await{
             Ok(user)=>{
                 Logger::debug_logger(handler_name, log_id, &token, "find_user_services.validate_token", &user);
                 if user.email != token.email{
-                    return Err("invalid token".to_string());
+                    Err(AuthError::Unauthorized(String::from("invalid token")))
                 }else{
                     Ok(user)
                 }
             },
             Err(error)=>{
-                Logger::warning_logger(handler_name, log_id, "find_user_services.validate_token", &error);
+                Logger::warning_logger(handler_name, log_id, "find_user_services.validate_token", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub fn revoke_access_token(
+        log_id: &str,
+        claims: &TokenClaims<AccessToken>,
+        redis_pool: &RedisPool
+    )-> Result<(), AuthError>{
+        let handler_name = "revoke_access_token_services";
+        match revoke_token(redis_pool, &claims.jti, claims.remaining_lifetime()).map_err(AuthError::Upstream){
+            Ok(_)=>{
+                Logger::info_logger(handler_name, log_id, "revoke_access_token_services.revoke_token");
+                Ok(())
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "revoke_access_token_services.revoke_token", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn logout(
+        log_id: &str,
+        refresh_token: &str,
+        claims: &TokenClaims<AccessToken>,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool
+    )-> Result<(), AuthError>{
+        let handler_name = "logout_services";
+        let stored_token = match UserQuery::find_refresh_token(refresh_token, claims.token.id, db_pool).await{
+            Ok(stored_token)=>stored_token,
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "logout_services.find_refresh_token", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        match UserQuery::delete_refresh_token_family(stored_token.family_id, db_pool).await{
+            Ok(_)=>Logger::info_logger(handler_name, log_id, "logout_services.delete_refresh_token_family"),
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "logout_services.delete_refresh_token_family", &error.to_string());
+                return Err(error)
+            }
+        }
+
+        Self::revoke_access_token(log_id, claims, redis_pool)
+    }
+
+    pub async fn logout_all(
+        log_id: &str,
+        claims: &TokenClaims<AccessToken>,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool
+    )-> Result<u64, AuthError>{
+        let handler_name = "logout_all_services";
+        let deleted = match UserQuery::delete_user_refresh_tokens(claims.token.id, db_pool).await{
+            Ok(deleted)=>{
+                Logger::info_logger(handler_name, log_id, "logout_all_services.delete_refresh_tokens");
+                deleted
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "logout_all_services.delete_refresh_tokens", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        Self::revoke_access_token(log_id, claims, redis_pool)?;
+        Ok(deleted)
+    }
+
+    pub async fn forgot_password(
+        log_id: &str,
+        data: ForgotPasswordData,
+        reset_token_lifetime: i64,
+        db_pool: &DbPool,
+        rabbit_pool: &RabbitMqPool
+    )-> Result<(), AuthError>{
+        let handler_name = "forgot_password_services";
+        let user = match UserQuery::find_user_by_email(&data.email, db_pool).await{
+            Ok(Some(user))=>user,
+            Ok(None)=>{
+                Logger::info_logger(handler_name, log_id, "forgot_password_services.unknown_email");
+                return Ok(())
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "forgot_password_services.find_user_by_email", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        let reset_token = generate_secret_token();
+        let expires_at = Utc::now() + chrono::Duration::seconds(reset_token_lifetime);
+
+        if let Err(error) = UserQuery::create_password_reset_token(&reset_token, user.id, expires_at, db_pool).await{
+            Logger::warning_logger(handler_name, log_id, "forgot_password_services.create_reset_token", &error.to_string());
+            return Err(error)
+        }
+
+        let message = PasswordResetMessage {
+            user_id: user.id,
+            email: user.email,
+            username: user.username,
+            reset_token,
+        };
+
+        match Self::publish_queue_message(log_id, PASSWORD_RESET_QUEUE, &message, rabbit_pool).await{
+            Ok(_)=>{
+                Logger::info_logger(handler_name, log_id, "forgot_password_services.publish_reset_message");
+                Ok(())
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "forgot_password_services.publish_reset_message", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn reset_password(
+        log_id: &str,
+        data: ResetPasswordData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<(), AuthError>{
+        let handler_name = "reset_password_services";
+        let password_hash = match hash_password(&data.password){
+            Ok(hash)=>hash,
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "reset_password_services.hash_password", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        let user_id = match UserQuery::reset_password(&data.token, &password_hash, db_pool).await{
+            Ok(user_id)=>{
+                Logger::info_logger(handler_name, log_id, "reset_password_services.reset_password");
+                user_id
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "reset_password_services.reset_password", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        Self::revoke_user_sessions(log_id, handler_name, user_id, Utc::now().timestamp(), db_pool, redis_pool, jwt_keyring).await
+    }
+
+    pub async fn update_profile(
+        log_id: &str,
+        token: &AccessToken,
+        data: UpdateProfileData,
+        verification_token_lifetime: i64,
+        db_pool: &DbPool,
+        rabbit_pool: &RabbitMqPool
+    )-> Result<RegisterPayload, AuthError>{
+        let handler_name = "update_profile_services";
+
+        if let Some(phone_number) = &data.phone_number {
+            if let Err(err)= phone_number.parse::<i128>(){
+                Logger::warning_logger(handler_name,log_id, "update_profile_services.parse_phone", &format!("{}",err));
+                return Err(AuthError::validation(format!("invalid phone number: {}",err)));
+            }
+        }
+
+        let (profile, email_changed) = match UserQuery::update_profile(token.id, &data, db_pool).await{
+            Ok(updated)=>{
+                Logger::debug_logger(handler_name, log_id, &data, "update_profile_services.update_profile", &updated.0);
+                updated
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "update_profile_services.update_profile", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        if email_changed {
+            match Self::send_verification_email(log_id, profile.id, &profile.email, &profile.username, verification_token_lifetime, db_pool, rabbit_pool).await {
+                Ok(_) => Logger::info_logger(handler_name, log_id, "update_profile_services.send_verification"),
+                Err(error) => Logger::warning_logger(handler_name, log_id, "update_profile_services.send_verification", &error.to_string()),
+            }
+        }
+
+        Ok(profile)
+    }
+
+    async fn confirm_password(
+        log_id: &str,
+        handler_name: &str,
+        user_id: Uuid,
+        password: &str,
+        db_pool: &DbPool
+    )-> Result<(), AuthError>{
+        let stored_password = match UserQuery::find_password_by_id(user_id, db_pool).await{
+            Ok(Some(password))=>password,
+            Ok(None)=>{
+                let error = AuthError::validation("no password set for this account, use password reset first");
+                Logger::warning_logger(handler_name, log_id, &format!("{}.find_password", handler_name), &error.to_string());
+                return Err(error)
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, &format!("{}.find_password", handler_name), &error.to_string());
                 return Err(error)
             }
+        };
+
+        let parsed_hash = match PasswordHash::new(&stored_password) {
+            Ok(parsed_hash) => parsed_hash,
+            Err(err) => {
+                Logger::warning_logger(handler_name, log_id, &format!("{}.password_validate", handler_name), &format!("{}",err));
+                return Err(AuthError::Internal(String::from("error parsing stored password hash")))
+            },
+        };
+
+        if let Err(err) = Argon2::default().verify_password(password.as_bytes(), &parsed_hash) {
+            Logger::warning_logger(handler_name, log_id, &format!("{}.password_validate", handler_name), &format!("{}",err));
+            return Err(AuthError::InvalidCredentials);
         }
+
+        Ok(())
+    }
+
+    pub async fn delete_account(
+        log_id: &str,
+        claims: &TokenClaims<AccessToken>,
+        data: DeleteAccountData,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        rabbit_pool: &RabbitMqPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<(), AuthError>{
+        let handler_name = "delete_account_services";
+        let user_id = claims.token.id;
+
+        Self::confirm_password(log_id, handler_name, user_id, &data.password, db_pool).await?;
+
+        if let Err(error) = revoke_subject_tokens(redis_pool, &claims.sub, Utc::now().timestamp(), jwt_keyring.lifetimes().access_token){
+            Logger::warning_logger(handler_name, log_id, "delete_account_services.revoke_tokens", &error.to_string());
+            return Err(AuthError::Upstream(error))
+        }
+
+        if let Err(error) = UserQuery::delete_user(user_id, db_pool).await{
+            Logger::warning_logger(handler_name, log_id, "delete_account_services.delete_user", &error.to_string());
+            return Err(error)
+        }
+        Logger::info_logger(handler_name, log_id, "delete_account_services.delete_user");
+
+        let event = UserDeletedEvent {
+            user_id,
+            deleted_at: Utc::now(),
+        };
+
+        match Self::publish_event(log_id, "user.deleted", &event, rabbit_pool).await{
+            Ok(_)=>Logger::info_logger(handler_name, log_id, "delete_account_services.publish_user_deleted"),
+            Err(error)=>Logger::err_logger(handler_name, log_id, "delete_account_services.publish_user_deleted", error.to_string()),
+        }
+
+        Ok(())
+    }
+
+    // Deletes every refresh token family and revokes the access tokens issued up to `issued_before`.
+    #[allow(clippy::too_many_arguments)]
+    async fn revoke_user_sessions(
+        log_id: &str,
+        handler_name: &str,
+        user_id: Uuid,
+        issued_before: i64,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<(), AuthError>{
+        if let Err(error) = UserQuery::delete_user_refresh_tokens(user_id, db_pool).await{
+            Logger::warning_logger(handler_name, log_id, &format!("{}.delete_refresh_tokens", handler_name), &error.to_string());
+            return Err(error)
+        }
+
+        match revoke_subject_tokens(redis_pool, &user_id.to_string(), issued_before, jwt_keyring.lifetimes().access_token){
+            Ok(_)=>{
+                Logger::info_logger(handler_name, log_id, &format!("{}.revoke_sessions", handler_name));
+                Ok(())
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, &format!("{}.revoke_sessions", handler_name), &error);
+                Err(AuthError::Upstream(error))
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn change_password(
+        log_id: &str,
+        claims: &TokenClaims<AccessToken>,
+        data: ChangePasswordData,
+        metadata: &SessionMetadata,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<LoginPayload, AuthError>{
+        let handler_name = "change_password_services";
+        let user_id = claims.token.id;
+
+        Self::confirm_password(log_id, handler_name, user_id, &data.current_password, db_pool).await?;
+
+        let password_hash = match hash_password(&data.new_password){
+            Ok(hash)=>hash,
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "change_password_services.hash_password", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        match UserQuery::update_password(user_id, &password_hash, db_pool).await{
+            Ok(_)=>Logger::info_logger(handler_name, log_id, "change_password_services.update_password"),
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "change_password_services.update_password", &error.to_string());
+                return Err(error)
+            }
+        }
+
+        // Every token issued up to now is revoked, the caller's included.
+        Self::revoke_user_sessions(log_id, handler_name, user_id, Utc::now().timestamp(), db_pool, redis_pool, jwt_keyring).await?;
+
+        let user = UserQuery::find_user_by_id(user_id, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "change_password_services.find_user_by_id", &error.to_string());
+        })?;
+
+        let payload = Self::issue_login_tokens(log_id, user, metadata, db_pool, jwt_keyring).await?;
+
+        // iat has one-second resolution, so the new access token can share the cut-off second; keep it by jti.
+        let new_claims = decode_access_token(&payload.access_token, jwt_keyring).map_err(|err|{
+            let error = AuthError::Internal(format!("error decode access token: {}", err));
+            Logger::warning_logger(handler_name, log_id, "change_password_services.decode_access_token", &error.to_string());
+            error
+        })?.claims;
+        exempt_token(redis_pool, &new_claims.jti, new_claims.remaining_lifetime()).map_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "change_password_services.exempt_token", &error);
+            AuthError::Upstream(error)
+        })?;
+
+        Ok(payload)
+    }
+
+    pub async fn verify_email(
+        log_id: &str,
+        token: &str,
+        db_pool: &DbPool
+    )-> Result<Uuid, AuthError>{
+        let handler_name = "verify_email_services";
+        match UserQuery::verify_email(token, db_pool).await{
+            Ok(user_id)=>{
+                Logger::info_logger(handler_name, log_id, "verify_email_services.verify_email");
+                Ok(user_id)
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "verify_email_services.verify_email", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn setup_totp(
+        log_id: &str,
+        token: &AccessToken,
+        mfa: &Mfa,
+        secret_cipher: &SecretCipher,
+        db_pool: &DbPool
+    )-> Result<TotpSetupPayload, AuthError>{
+        let handler_name = "setup_totp_services";
+        let secret = generate_secret();
+        let encrypted_secret = secret_cipher.encrypt(&secret).map_err(AuthError::Internal)?;
+
+        match UserQuery::set_totp_pending_secret(token.id, &encrypted_secret, db_pool).await{
+            Ok(true)=>{
+                Logger::info_logger(handler_name, log_id, "setup_totp_services.set_totp_pending_secret");
+                Ok(TotpSetupPayload {
+                    secret: encode_secret(&secret),
+                    otpauth_uri: otpauth_uri(&mfa.issuer, &token.email, &secret),
+                })
+            },
+            Ok(false)=>{
+                let error = AuthError::Conflict(String::from("two-factor authentication already enabled"));
+                Logger::warning_logger(handler_name, log_id, "setup_totp_services.set_totp_pending_secret", &error.to_string());
+                Err(error)
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "setup_totp_services.set_totp_pending_secret", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    // Returns the plaintext recovery codes; only their hashes are stored, so this is the one chance to show them.
+    pub async fn confirm_totp(
+        log_id: &str,
+        token: &AccessToken,
+        code: &str,
+        mfa: &Mfa,
+        secret_cipher: &SecretCipher,
+        db_pool: &DbPool
+    )-> Result<Vec<String>, AuthError>{
+        let handler_name = "confirm_totp_services";
+        let totp = UserQuery::find_totp(token.id, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "confirm_totp_services.find_totp", &error.to_string());
+        })?;
+
+        if totp.totp_enabled {
+            return Err(AuthError::Conflict(String::from("two-factor authentication already enabled")));
+        }
+        let pending_secret = totp.totp_pending_secret.ok_or(AuthError::validation("two-factor setup not started"))?;
+        let secret = secret_cipher.decrypt(&pending_secret).map_err(AuthError::Internal)?;
+
+        let counter = match verify_code(&secret, code, Utc::now().timestamp()){
+            Some(counter)=>counter,
+            None=>{
+                let error = AuthError::validation("invalid code");
+                Logger::warning_logger(handler_name, log_id, "confirm_totp_services.verify_code", &error.to_string());
+                return Err(error)
+            }
+        };
+
+        let recovery_codes = generate_recovery_codes(mfa.recovery_codes);
+        let recovery_code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_token(&normalize_recovery_code(code))).collect();
+
+        match UserQuery::enable_totp(token.id, &pending_secret, counter, &recovery_code_hashes, db_pool).await{
+            Ok(true)=>{
+                Logger::info_logger(handler_name, log_id, "confirm_totp_services.enable_totp");
+                Ok(recovery_codes)
+            },
+            Ok(false)=>{
+                let error = AuthError::Conflict(String::from("two-factor setup changed, start again"));
+                Logger::warning_logger(handler_name, log_id, "confirm_totp_services.enable_totp", &error.to_string());
+                Err(error)
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "confirm_totp_services.enable_totp", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    async fn check_second_factor(
+        user_id: Uuid,
+        data: &MfaVerifyData,
+        secret_cipher: &SecretCipher,
+        db_pool: &DbPool
+    )-> Result<bool, AuthError>{
+        match (&data.code, &data.recovery_code){
+            (Some(code), _)=>{
+                let totp = UserQuery::find_totp(user_id, db_pool).await?;
+                let secret = match (totp.totp_enabled, totp.totp_secret){
+                    (true, Some(secret))=>secret_cipher.decrypt(&secret).map_err(AuthError::Internal)?,
+                    _=>return Err(AuthError::Unauthorized(String::from("two-factor authentication not enabled")))
+                };
+                match verify_code(&secret, code, Utc::now().timestamp()){
+                    Some(counter)=>UserQuery::record_totp_counter(user_id, counter, db_pool).await,
+                    None=>Ok(false)
+                }
+            },
+            (None, Some(recovery_code))=>{
+                UserQuery::consume_recovery_code(user_id, &hash_token(&normalize_recovery_code(recovery_code)), db_pool).await
+            },
+            (None, None)=>Err(AuthError::validation("code or recovery_code is required"))
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn verify_mfa(
+        log_id: &str,
+        data: MfaVerifyData,
+        metadata: &SessionMetadata,
+        login_limit: &LoginLimit,
+        secret_cipher: &SecretCipher,
+        db_pool: &DbPool,
+        redis_pool: &RedisPool,
+        jwt_keyring: &JwtKeyring
+    )-> Result<LoginPayload, AuthError>{
+        let handler_name = "verify_mfa_services";
+        let claims = decode_mfa_pending_token(&data.mfa_token, jwt_keyring).map_err(|err|{
+            let error = token_error(err);
+            Logger::warning_logger(handler_name, log_id, "verify_mfa_services.decode_token", &error.to_string());
+            error
+        })?.claims;
+
+        if is_token_revoked(redis_pool, &claims.jti, &claims.sub, claims.iat).map_err(AuthError::Upstream)? {
+            let error = token_error(JwtLibError::Revoked);
+            Logger::warning_logger(handler_name, log_id, "verify_mfa_services.is_token_revoked", &error.to_string());
+            return Err(error);
+        }
+
+        if !Self::check_second_factor(claims.token.id, &data, secret_cipher, db_pool).await? {
+            // Each pending token only gets a few guesses; the login limiter bounds how often a new one can be obtained.
+            let attempts = increment_counter(redis_pool, &format!("{}:{}", MFA_ATTEMPTS_PREFIX, claims.jti), claims.remaining_lifetime().max(1)).map_err(AuthError::Upstream)?;
+            if attempts >= login_limit.max_failures {
+                revoke_token(redis_pool, &claims.jti, claims.remaining_lifetime()).map_err(AuthError::Upstream)?;
+            }
+            let error = AuthError::Unauthorized(String::from("invalid code"));
+            Logger::warning_logger(handler_name, log_id, "verify_mfa_services.check_second_factor", &error.to_string());
+            return Err(error);
+        }
+
+        revoke_token(redis_pool, &claims.jti, claims.remaining_lifetime()).map_err(AuthError::Upstream)?;
+        Logger::info_logger(handler_name, log_id, "verify_mfa_services.check_second_factor");
+
+        let user = UserQuery::find_user_by_id(claims.token.id, db_pool).await.inspect_err(|error|{
+            Logger::warning_logger(handler_name, log_id, "verify_mfa_services.find_user_by_id", &error.to_string());
+        })?;
+
+        Self::issue_login_tokens(log_id, user, metadata, db_pool, jwt_keyring).await
+    }
+
+    pub async fn list_sessions(
+        log_id: &str,
+        token: &AccessToken,
+        db_pool: &DbPool
+    )-> Result<Vec<SessionPayload>, AuthError>{
+        let handler_name = "list_sessions_services";
+        match UserQuery::find_user_sessions(token.id, db_pool).await{
+            Ok(sessions)=>{
+                Logger::info_logger(handler_name, log_id, "list_sessions_services.find_user_sessions");
+                Ok(sessions)
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "list_sessions_services.find_user_sessions", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn revoke_session(
+        log_id: &str,
+        session_id: Uuid,
+        token: &AccessToken,
+        db_pool: &DbPool
+    )-> Result<(), AuthError>{
+        let handler_name = "revoke_session_services";
+        match UserQuery::delete_user_session(session_id, token.id, db_pool).await{
+            Ok(0)=>{
+                let error = AuthError::NotFound(String::from("session not found"));
+                Logger::warning_logger(handler_name, log_id, "revoke_session_services.delete_user_session", &error.to_string());
+                Err(error)
+            },
+            Ok(_)=>{
+                Logger::info_logger(handler_name, log_id, "revoke_session_services.delete_user_session");
+                Ok(())
+            },
+            Err(error)=>{
+                Logger::warning_logger(handler_name, log_id, "revoke_session_services.delete_user_session", &error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    pub fn spawn_refresh_token_purge(
+        db_pool: DbPool,
+        interval: Duration
+    ){
+        let handler_name = "purge_refresh_token_services";
+        actix_web::rt::spawn(async move {
+            loop {
+                match UserQuery::purge_expired_refresh_tokens(&db_pool).await{
+                    Ok(purged)=>Logger::info_logger(handler_name, "purge", &format!("purge_refresh_token_services.purged.{}",purged)),
+                    Err(error)=>Logger::warning_logger(handler_name, "purge", "purge_refresh_token_services.purge", &error.to_string())
+                }
+                actix_web::rt::time::sleep(interval).await;
+            }
+        });
     }
 
     //register queue
     pub async fn register(
         log_id:&str,
         mut data: RegisterData,
+        verification_token_lifetime: i64,
         db_pool: &DbPool,
         rabbit_pool: &RabbitMqPool,
-    ) -> Result<RegisterPayload, String> {
-        let argon2 = Argon2::default();
-        let salt: SaltString = SaltString::generate(&mut OsRng);
+    ) -> Result<RegisterPayload, AuthError> {
         let handler_name= "register_service";
-        let password_hash = match argon2.hash_password(data.password.as_bytes(), &salt) {
+        let password_hash = match hash_password(&data.password) {
             Ok(hash) => {
                 Logger::info_logger(handler_name,log_id, "register.hash_password");
-                hash.to_string()
+                hash
             },
             Err(e) => {
-                Logger::warning_logger(handler_name,log_id, "register.hash_password", &format!("{}",e));
-                return Err(format!("Password hash error: {}", e))
+                Logger::warning_logger(handler_name,log_id, "register.hash_password", &e.to_string());
+                return Err(e)
             }
         };
 
@@ -245,55 +958,176 @@ impl UserServices {
 
         if let Err(err)= data.phone_number.parse::<i128>(){
             Logger::warning_logger(handler_name,log_id, "register.parse_phone", &format!("{}",err));
-            return Err(format!("input error: {}",err));
+            return Err(AuthError::validation(format!("invalid phone number: {}",err)));
         }
 
+        let register_payload = match UserQuery::create_user(data.clone(), db_pool).await {
+            Ok(register_payload) => {
+                Logger::debug_logger(handler_name,log_id, &data, "register.create_user", &register_payload);
+                Logger::info_logger(handler_name,log_id, "register.create_user");
+                register_payload
+            },
+            Err(error) => {
+                Logger::warning_logger(handler_name,log_id, "register.create_user", &error.to_string());
+                return Err(error)
+            },
+        };
+
+        let published = Self::send_verification_email(
+            log_id,
+            register_payload.id,
+            &register_payload.email,
+            &register_payload.username,
+            verification_token_lifetime,
+            db_pool,
+            rabbit_pool
+        ).await;
+
+        if let Err(error) = published {
+            Logger::warning_logger(handler_name,log_id, "register.publish_register_message", &error.to_string());
+            if let Err(delete_error) = UserQuery::delete_user(register_payload.id, db_pool).await {
+                Logger::warning_logger(handler_name,log_id, "register.rollback_user", &delete_error.to_string());
+            }
+            return Err(error)
+        }
+
+        Ok(register_payload)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn send_verification_email(
+        log_id: &str,
+        user_id: Uuid,
+        email: &str,
+        username: &str,
+        verification_token_lifetime: i64,
+        db_pool: &DbPool,
+        rabbit_pool: &RabbitMqPool
+    )-> Result<(), AuthError>{
+        let verification_token = generate_secret_token();
+        let expires_at = Utc::now() + chrono::Duration::seconds(verification_token_lifetime);
+
+        UserQuery::create_email_verification_token(&verification_token, user_id, expires_at, db_pool).await?;
+
+        let message = RegisterMessage {
+            user_id,
+            email: email.to_string(),
+            username: username.to_string(),
+            verification_token,
+        };
+        Self::publish_queue_message(log_id, REGISTER_QUEUE, &message, rabbit_pool).await
+    }
+
+    async fn publish_event<T: Serialize>(
+        log_id:&str,
+        routing_key: &str,
+        event: &T,
+        rabbit_pool: &RabbitMqPool,
+    ) -> Result<(), AuthError> {
+        let handler_name= "publish_event";
+        let conn = rabbit_pool.get().await.map_err(|err| {
+            Logger::err_logger(handler_name,log_id, "publish_event.get_rabbit_connections", &err);
+            AuthError::Upstream(format!("RabbitMQ connection error: {}", err))
+        })?;
+
+        let channel: lapin::Channel = conn.create_channel().await.map_err(|err| {
+            Logger::err_logger(handler_name,log_id, "publish_event.create_rmq_channel", &err);
+            AuthError::Upstream(format!("RabbitMQ channel error: {}", err))
+        })?;
+
+        channel
+            .exchange_declare(
+                USER_EVENTS_EXCHANGE,
+                ExchangeKind::Topic,
+                ExchangeDeclareOptions { durable: true, ..Default::default() },
+                FieldTable::default(),
+            )
+            .await
+            .map_err(|err| {
+                Logger::err_logger(handler_name,log_id, "publish_event.declare_exchange", &err);
+                AuthError::Upstream(format!("RabbitMQ exchange declare error: {}", err))
+            })?;
+
+        channel.confirm_select(ConfirmSelectOptions::default()).await.map_err(|err| {
+            Logger::err_logger(handler_name,log_id, "publish_event.confirm_select", &err);
+            AuthError::Upstream(format!("RabbitMQ confirm select error: {}", err))
+        })?;
+
+        let payload_bytes = json!(event).to_string().into_bytes();
+
+        let confirm = channel
+            .basic_publish(
+                USER_EVENTS_EXCHANGE,
+                routing_key,
+                BasicPublishOptions::default(),
+                &payload_bytes,
+                BasicProperties::default().with_delivery_mode(2),
+            )
+            .await
+            .map_err(|err| AuthError::Upstream(format!("RabbitMQ publish error: {}", err)))?;
+
+        let confirmation: Confirmation = confirm.await.map_err(|err| AuthError::Upstream(format!("RabbitMQ confirm error: {}", err)))?;
+        if confirmation.is_nack() {
+            Logger::warning_logger(handler_name,log_id, "publish_event.publish_confirm", "message nacked by the broker");
+            return Err(AuthError::Upstream(String::from("RabbitMQ publish was nacked by the broker")));
+        }
+
+        info!("✅ Successfully published {} to {}", routing_key, USER_EVENTS_EXCHANGE);
+        Ok(())
+    }
+
+    async fn publish_queue_message<T: Serialize>(
+        log_id:&str,
+        queue: &str,
+        message: &T,
+        rabbit_pool: &RabbitMqPool,
+    ) -> Result<(), AuthError> {
+        let handler_name= "publish_queue_message";
         let conn = match rabbit_pool.get().await {
             Ok(conn) => {
-                Logger::info_logger(handler_name,log_id, "register.get_rabbit_connections");
+                Logger::info_logger(handler_name,log_id, "publish_queue_message.get_rabbit_connections");
                 conn
             },
             Err(err) => {
-                Logger::err_logger(handler_name,log_id, "register.get_rabbit_connections", &err);
-                return Err(format!("RabbitMQ connection error: {}", err));
+                Logger::err_logger(handler_name,log_id, "publish_queue_message.get_rabbit_connections", &err);
+                return Err(AuthError::Upstream(format!("RabbitMQ connection error: {}", err)));
             }
         };
 
         let channel: lapin::Channel = match conn.create_channel().await {
             Ok(channel) => {
-                Logger::info_logger(handler_name,log_id, "register.create_rmq_channel");
+                Logger::info_logger(handler_name,log_id, "publish_queue_message.create_rmq_channel");
                 channel
             },
             Err(err) => {
-                Logger::err_logger(handler_name,log_id, "register.create_rmq_channel", &err);
-                return Err(format!("RabbitMQ channel error: {}", err));
+                Logger::err_logger(handler_name,log_id, "publish_queue_message.create_rmq_channel", &err);
+                return Err(AuthError::Upstream(format!("RabbitMQ channel error: {}", err)));
             }
         };
 
         if let Err(err) = channel
             .queue_declare(
-                "register_queue",
+                queue,
                 lapin::options::QueueDeclareOptions::default(),
                 FieldTable::default(),
             )
             .await
         {
-            Logger::err_logger(handler_name,log_id, "register.create_queue", &err);
-            return Err(format!("RabbitMQ queue declare error: {}", err));
+            Logger::err_logger(handler_name,log_id, "publish_queue_message.create_queue", &err);
+            return Err(AuthError::Upstream(format!("RabbitMQ queue declare error: {}", err)));
         }
 
-        let payload = json!({
-            "email": data.email,
-            "username": data.username,
-            "password": data.password,
-        });
+        if let Err(err) = channel.confirm_select(ConfirmSelectOptions::default()).await {
+            Logger::err_logger(handler_name,log_id, "publish_queue_message.confirm_select", &err);
+            return Err(AuthError::Upstream(format!("RabbitMQ confirm select error: {}", err)));
+        }
 
-        let payload_bytes = payload.to_string().into_bytes();
+        let payload_bytes = json!(message).to_string().into_bytes();
 
-        let _: PublisherConfirm = match channel
+        let confirmation: Confirmation = match channel
             .basic_publish(
                 "",
-                "register_queue",
+                queue,
                 BasicPublishOptions::default(),
                 &payload_bytes,
                 BasicProperties::default(),
@@ -301,25 +1135,19 @@ impl UserServices {
             .await
         {
             Ok(confirm) => {
-                confirm
+                confirm.await.map_err(|err| AuthError::Upstream(format!("RabbitMQ confirm error: {}", err)))?
             },
             Err(err) => {
-                return Err(format!("RabbitMQ publish error: {}", err));
+                return Err(AuthError::Upstream(format!("RabbitMQ publish error: {}", err)));
             }
         };
 
-        info!("✅ Successfully published register request to queue");
-
-        match UserQuery::create_user(data.clone(), db_pool).await {
-            Ok(register_payload) => {
-                Logger::debug_logger(handler_name,log_id, &data, "register.create_user", &register_payload);
-                Logger::info_logger(handler_name,log_id, "register.create_user");
-                Ok(register_payload)
-            },
-            Err(error) => {
-                Logger::warning_logger(handler_name,log_id, "register.create_user", &format!("{}",error));
-                Err(format!("{}", error))
-        },
+        if confirmation.is_nack() {
+            Logger::warning_logger(handler_name,log_id, "publish_queue_message.publish_confirm", "message nacked by the broker");
+            return Err(AuthError::Upstream(String::from("RabbitMQ publish was nacked by the broker")));
         }
+
+        info!("✅ Successfully published message to {}", queue);
+        Ok(())
     }
 }
\ No newline at end of file
@@ -10,6 +10,14 @@ pub struct Grpc{
     pub url: String
 }
 
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct ServiceAuth{
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: String
+}
+
 #[derive(Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct Logger{
     log: String
@@ -19,5 +27,6 @@ pub struct Logger{
 pub struct PostGatewayAppConfig{
  pub logger: Logger,
  pub grpc: Grpc,
- pub kafka: Kafka
+ pub kafka: Kafka,
+ pub service_auth: ServiceAuth
 }
\ No newline at end of file
@@ -4,12 +4,11 @@ use actix_web::{
     App, HttpServer
 };
 use config_type::PostGatewayAppConfig;
-use modules::post::handler::{post_config, protected_post_config};
+use modules::post::{handler::{post_config, protected_post_config}, interceptor::ServiceTokenInterceptor};
 use dotenv::dotenv;
-use env_logger; 
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use tonic::transport::Channel;
+use tonic::{codegen::InterceptedService, transport::Channel};
 use kafka_libs::{Producer,configure_kafka};
 mod config_type;
 mod modules;
@@ -20,8 +19,8 @@ use proto_libs::post_proto::{post_client::PostClient, protected_post_client::Pro
 
 // Define AppState with both PostClient and ProtectedPostClient
 pub struct AppState {
-    post_client: Arc<Mutex<PostClient<Channel>>>,
-    protected_post_client: Arc<Mutex<ProtectedPostClient<Channel>>>,
+    post_client: Arc<Mutex<PostClient<InterceptedService<Channel, ServiceTokenInterceptor>>>>,
+    protected_post_client: Arc<Mutex<ProtectedPostClient<InterceptedService<Channel, ServiceTokenInterceptor>>>>,
     kafka_producer: Producer
 }
 
@@ -34,7 +33,7 @@ async fn main() -> std::io::Result<()> {
     let config_path = match dotenv::var("CONFIG_PATH"){
         Ok(path)=>path,
         Err(err)=>{
-            ServiceLogger::err_logger(&handler_name, "main", "post_gateway.get_path", &err);
+            ServiceLogger::err_logger(handler_name, "main", "post_gateway.get_path", &err);
             panic!("{}",err)
         }
     };
@@ -42,33 +41,42 @@ async fn main() -> std::io::Result<()> {
     let config:PostGatewayAppConfig = match config_libs::libs_config(&config_path, "POST-GATEWAY"){
         Ok(config)=>config,
         Err(error)=>{
-            ServiceLogger::err_logger(&handler_name, "main", "postgateway.get_config", &error);
+            ServiceLogger::err_logger(handler_name, "main", "postgateway.get_config", &error);
             panic!("{}",error);
         }
     };
 
     let grpc_url = config.grpc.url;
 
-    let post_client = match PostClient::connect(grpc_url.clone()).await{
-        Ok(client)=>client,
+    let service_token = match ServiceTokenInterceptor::connect(config.service_auth).await{
+        Ok(interceptor)=>interceptor,
         Err(error)=>{
-            ServiceLogger::err_logger(&handler_name, "main", "postgateway.get_postclient", &error);
+            ServiceLogger::err_logger(handler_name, "main", "postgateway.get_service_token", &error);
             panic!("{}",error);
         }
     };
-    let protected_post_client = match ProtectedPostClient::connect(grpc_url.clone()).await {
-        Ok(protected_client)=>protected_client,
+
+    let channel = match Channel::from_shared(grpc_url) {
+        Ok(endpoint)=>match endpoint.connect().await{
+            Ok(channel)=>channel,
+            Err(error)=>{
+                ServiceLogger::err_logger(handler_name, "main", "postgateway.get_channel", &error);
+                panic!("{}",error);
+            }
+        },
         Err(error)=>{
-            ServiceLogger::err_logger(&handler_name, "main", "postgateaway.get_protected_lclien", &error);
-            panic!("{}",error)
+            ServiceLogger::err_logger(handler_name, "main", "postgateway.get_channel", &error);
+            panic!("{}",error);
         }
     };
+    let post_client = PostClient::with_interceptor(channel.clone(), service_token.clone());
+    let protected_post_client = ProtectedPostClient::with_interceptor(channel, service_token);
 
     let kafka_url = config.kafka.host;
     let kafka_config = match configure_kafka(kafka_url).await{
         Ok(kafka)=> kafka,
         Err(error)=>{
-            ServiceLogger::err_logger(&handler_name, "main", "postgateaway.get_kafka_config", &error);
+            ServiceLogger::err_logger(handler_name, "main", "postgateaway.get_kafka_config", &error);
             panic!("{}",error)
         }
     };
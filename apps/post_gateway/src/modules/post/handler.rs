@@ -1,11 +1,12 @@
 use actix_web::{
-    delete, get, patch, post, web::{scope, Data, Json, Path, Query, ServiceConfig}, HttpResponse, Responder
+    delete, get, http::header::AUTHORIZATION, patch, post, web::{scope, Data, Json, Path, Query, ServiceConfig}, HttpRequest, HttpResponse, Responder
 };
 use kafka_libs::{send_message, Producer};
 use logger_libs::Logger;
 use serde_json::json;
 use uuid::Uuid;
 use proto_libs::post_proto;
+use tonic::{metadata::MetadataValue, Code, Status};
 
 use crate::{
     modules::post::model::{CreatePostRequest, Pagination, PostResponse}, AppState
@@ -26,18 +27,62 @@ pub async fn send_event(
 
     match send_message(producer_ref, topic, &key, &message).await {
         Ok(_) => {
-            Logger::info_logger("post_gateway.send_handler", &format!("{}",key), "send_event.send_message");
+            Logger::info_logger("post_gateway.send_handler", &key, "send_event.send_message");
             Ok(())
         },
         Err(error) => {
-            Logger::err_logger("post_gateway.send_handler", &format!("{}",key), "send_event.send_message",error);
+            Logger::err_logger("post_gateway.send_handler", &key, "send_event.send_message",error);
             Err(HttpResponse::InternalServerError().json("Failed to send message to Kafka"))
         },
     }
 }
 
+pub fn authorized_request<T>(http_req: &HttpRequest, message: T) -> Result<tonic::Request<T>, HttpResponse> {
+    let authorization = match http_req.headers().get(AUTHORIZATION).and_then(|value| value.to_str().ok()) {
+        Some(value) if value.starts_with("Bearer ") => value,
+        _ => return Err(HttpResponse::Unauthorized().json(json!({
+            "error": "Unauthorized",
+            "message": "missing bearer token"
+        }))),
+    };
+
+    let metadata_value = match MetadataValue::try_from(authorization) {
+        Ok(value) => value,
+        Err(_) => return Err(HttpResponse::Unauthorized().json(json!({
+            "error": "Unauthorized",
+            "message": "invalid authorization header"
+        }))),
+    };
+
+    let mut request = tonic::Request::new(message);
+    request.metadata_mut().insert("authorization", metadata_value);
+    Ok(request)
+}
+
+pub fn grpc_error_response(message: &str, error: &Status) -> HttpResponse {
+    match error.code() {
+        Code::Unauthenticated => HttpResponse::Unauthorized().json(json!({
+            "error": "Unauthorized",
+            "message": error.message()
+        })),
+        Code::PermissionDenied => HttpResponse::Forbidden().json(json!({
+            "error": "Forbidden",
+            "message": error.message()
+        })),
+        Code::Unavailable => HttpResponse::ServiceUnavailable().json(json!({
+            "error": "Service Unavailable",
+            "message": error.message()
+        })),
+        _ => HttpResponse::BadRequest().json(json!({
+            "message": message,
+            "error": format!("{}", error)
+        })),
+    }
+}
+
 #[post("/create_post")]
 pub async fn create_post(
+    http_req: HttpRequest,
     data: Data<AppState>,
     content: Json<CreatePostRequest>
 ) -> impl Responder {
@@ -50,7 +95,10 @@ pub async fn create_post(
 
     let log_id= &format!("post_gateway.create_post.{}",&request_data.title);
 
-    let request = tonic::Request::new(request_data);
+    let request = match authorized_request(&http_req, request_data) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
     
     let response = {
         let mut client = data.protected_post_client.lock().await;
@@ -69,20 +117,17 @@ pub async fn create_post(
             };
             let kafka_message = String::from("post created");
             let _ = send_event(&data.kafka_producer,response.id , response.user_id, kafka_message).await;
-            Logger::info_logger(&handler_name, log_id,"post_gateway.create_post.insert_services");
+            Logger::info_logger(handler_name, log_id,"post_gateway.create_post.insert_services");
         
-            Logger::debug_logger(&handler_name, log_id, req, "post_gateway.create_post.insert_services", &response);
+            Logger::debug_logger(handler_name, log_id, req, "post_gateway.create_post.insert_services", &response);
             HttpResponse::Created().json(json!({
                 "message": "post created",
                 "data": response
             }))
         },
         Err(error) => {
-            Logger::warning_logger(&handler_name, log_id, "post_gateway.create_post.insert_services", &format!("{}",error));
-            HttpResponse::BadRequest().json(json!({
-                "message": "create post failed",
-                "error": format!("{}", error)
-            }))
+            Logger::warning_logger(handler_name, log_id, "post_gateway.create_post.insert_services", &format!("{}",error));
+            grpc_error_response("create post failed", &error)
         }
     }
     
@@ -90,6 +135,7 @@ pub async fn create_post(
 
 #[patch("/update_post/{post_id}")]
 pub async fn update_post(
+    http_req: HttpRequest,
     data: Data<AppState>,
     path: Path<Uuid>,
     content: Json<CreatePostRequest>
@@ -105,7 +151,10 @@ pub async fn update_post(
             content: req.content.clone(),  
     };
 
-    let request = tonic::Request::new(request_data);
+    let request = match authorized_request(&http_req, request_data) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
 
     let response = {
         let mut client = data.protected_post_client.lock().await;
@@ -123,8 +172,8 @@ pub async fn update_post(
                 username:message.username
             };
 
-            Logger::debug_logger(&handler_name, log_id, req, "post_gateway.update_in_services", &response);
-            Logger::info_logger(&handler_name, log_id, "post_gateway.update_in_services");
+            Logger::debug_logger(handler_name, log_id, req, "post_gateway.update_in_services", &response);
+            Logger::info_logger(handler_name, log_id, "post_gateway.update_in_services");
 
             let kafka_message = String::from("post updated");
             let _ = send_event(&data.kafka_producer,response.id , response.user_id, kafka_message).await;
@@ -135,17 +184,15 @@ pub async fn update_post(
             }))
         },
         Err(error) => {
-            Logger::err_logger(&handler_name, log_id, "post_gateway.update_in_services", &error);
-            HttpResponse::BadRequest().json(json!({
-                "message": "create post failed",
-                "error": format!("{}", error)
-            }))
+            Logger::err_logger(handler_name, log_id, "post_gateway.update_in_services", &error);
+            grpc_error_response("create post failed", &error)
         }
     }
 }
 
 #[delete("/delete_post/{post_id}")]
 pub async fn delete_post(
+    http_req: HttpRequest,
     data: Data<AppState>,
     path: Path<Uuid>
 ) -> impl Responder {
@@ -157,7 +204,10 @@ pub async fn delete_post(
             post_id: post_id.to_string()
     };
 
-    let request = tonic::Request::new(request_data);
+    let request = match authorized_request(&http_req, request_data) {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
 
     let response = {
         let mut client = data.protected_post_client.lock().await;
@@ -168,9 +218,9 @@ pub async fn delete_post(
         Ok(message) => {
             let message = message.into_inner();
             let response_message = format!("post: {} successfully deleted",message.post_id);
-            Logger::debug_logger(&handler_name, log_id, &post_id, "post_gateway.delete_post_in_services", &response_message);
+            Logger::debug_logger(handler_name, log_id, &post_id, "post_gateway.delete_post_in_services", &response_message);
 
-            Logger::info_logger(&handler_name, log_id, "post_gateway.delete_post_in_services");
+            Logger::info_logger(handler_name, log_id, "post_gateway.delete_post_in_services");
             let kafka_message = String::from("post created");
             let _ = send_event(&data.kafka_producer,message.post_id.parse::<Uuid>().unwrap() , message.user_id.parse::<Uuid>().unwrap(), kafka_message).await;
 
@@ -179,11 +229,8 @@ pub async fn delete_post(
             }))
         },
         Err(error) => {
-            Logger::err_logger(&handler_name, log_id, "post_gateway.delete_post_in_services",&error);
-            HttpResponse::BadRequest().json(json!({
-                "message": "create post failed",
-                "error": format!("{}", error)
-            }))
+            Logger::err_logger(handler_name, log_id, "post_gateway.delete_post_in_services",&error);
+            grpc_error_response("create post failed", &error)
         }
     }
     
@@ -232,7 +279,7 @@ pub async fn get_all_post(
             })
             .collect();
             
-            Logger::info_logger(&handler_name, log_id, "post_gateway.get_all_post_in_post_services");
+            Logger::info_logger(handler_name, log_id, "post_gateway.get_all_post_in_post_services");
             HttpResponse::Ok().json(json!({
                 "message": "fetch all posts success",
                 "limits": limits,
@@ -241,7 +288,7 @@ pub async fn get_all_post(
             }))
         },
         Err(error) => {
-            Logger::warning_logger(&handler_name, log_id, "post_gateway.get_all_post_in_post_services",&format!("{}",error));
+            Logger::warning_logger(handler_name, log_id, "post_gateway.get_all_post_in_post_services",&format!("{}",error));
             HttpResponse::BadRequest().json(json!({
                 "message": "fetch all posts failed",
                 "error": format!("{}", error)
@@ -279,14 +326,14 @@ pub async fn get_post_by_id(
                 username: message.username
             };
 
-            Logger::info_logger(&handler_name, log_id, "get_post_by_id_in_post_services");
+            Logger::info_logger(handler_name, log_id, "get_post_by_id_in_post_services");
             HttpResponse::Ok().json(json!({
                 "message": "fetch post success",
                 "data": post
             }))
         },
         Err(error) => {
-            Logger::warning_logger(&handler_name, log_id, "get_post_by_id_in_post_services",&format!("{}",error));
+            Logger::warning_logger(handler_name, log_id, "get_post_by_id_in_post_services",&format!("{}",error));
 
             HttpResponse::NotFound().json(json!({
                 "message": "post not found",
@@ -0,0 +1,87 @@
+use std::{sync::{Arc, RwLock}, time::Duration};
+
+use logger_libs::Logger;
+use serde::Deserialize;
+use tonic::{metadata::MetadataValue, service::Interceptor, Request, Status};
+
+use crate::config_type::ServiceAuth;
+
+const REFRESH_MARGIN_SECONDS: i64 = 30;
+const RETRY_SECONDS: u64 = 5;
+
+#[derive(Debug, Deserialize)]
+struct ServiceTokenResponse {
+    access_token: String,
+    expires_in: i64
+}
+
+// Attaches the gateway's own client_credentials token to every call into post_services.
+#[derive(Clone)]
+pub struct ServiceTokenInterceptor {
+    token: Arc<RwLock<String>>
+}
+
+async fn fetch_token(http: &reqwest::Client, config: &ServiceAuth) -> Result<ServiceTokenResponse, String> {
+    let response = http.post(&config.token_url)
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&[("grant_type", "client_credentials"), ("scope", config.scope.as_str())])
+        .send().await
+        .map_err(|error| format!("token request failed: {}", error))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(format!("token endpoint returned {}: {}", status, body));
+    }
+
+    response.json::<ServiceTokenResponse>().await
+        .map_err(|error| format!("invalid token response: {}", error))
+}
+
+impl ServiceTokenInterceptor {
+    pub async fn connect(config: ServiceAuth) -> Result<Self, String> {
+        let http = reqwest::Client::new();
+        let first = fetch_token(&http, &config).await?;
+
+        let interceptor = ServiceTokenInterceptor { token: Arc::new(RwLock::new(first.access_token)) };
+        let token = interceptor.token.clone();
+        tokio::spawn(async move {
+            let mut expires_in = first.expires_in;
+            loop {
+                tokio::time::sleep(Duration::from_secs((expires_in - REFRESH_MARGIN_SECONDS).max(RETRY_SECONDS as i64) as u64)).await;
+                expires_in = match fetch_token(&http, &config).await {
+                    Ok(response) => {
+                        if let Ok(mut current) = token.write() {
+                            *current = response.access_token;
+                        }
+                        Logger::info_logger("post_gateway.service_token", &config.client_id, "service_token.refresh");
+                        response.expires_in
+                    },
+                    Err(error) => {
+                        Logger::err_logger("post_gateway.service_token", &config.client_id, "service_token.refresh", &error);
+                        RETRY_SECONDS as i64
+                    }
+                };
+            }
+        });
+
+        Ok(interceptor)
+    }
+}
+
+impl Interceptor for ServiceTokenInterceptor {
+    fn call(&mut self, mut req: Request<()>) -> Result<Request<()>, Status> {
+        let token = match self.token.read() {
+            Ok(token) => token.clone(),
+            Err(_) => return Err(Status::unavailable("service token is not available")),
+        };
+
+        match MetadataValue::try_from(format!("Bearer {}", token)) {
+            Ok(value) => {
+                req.metadata_mut().insert("x-service-authorization", value);
+                Ok(req)
+            },
+            Err(_) => Err(Status::internal("invalid service token")),
+        }
+    }
+}
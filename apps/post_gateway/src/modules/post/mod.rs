@@ -1,2 +1,3 @@
 pub mod handler;
+pub mod interceptor;
 pub mod model;
@@ -1,3 +1,4 @@
+use jwt_libs::types::JwtClaimsConfig;
 use serde::Deserialize;
 
 #[derive(Deserialize, Debug, Default, PartialEq, Eq)]
@@ -24,6 +25,12 @@ pub struct Redis{
     pub max_pool_connection: u32
 }
 
+#[derive(Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct Jwks{
+    pub url: String,
+    pub refresh_interval: u64
+}
+
 #[derive(Deserialize, Debug, Default, PartialEq, Eq)]
 pub struct Logger{
     log: String
@@ -34,5 +41,7 @@ pub struct PostAppConfig{
     pub apps: Apps,
     pub database: Database,
     pub redis: Redis,
-    pub logger: Logger
+    pub logger: Logger,
+    pub jwt: JwtClaimsConfig,
+    pub jwks: Jwks
 }
@@ -1,9 +1,9 @@
-use std::{env::var, error::Error, process::exit, sync::Arc};
+use std::{env::var, error::Error, sync::Arc, time::Duration};
 
 use config_libs::libs_config;
 use config_type::PostAppConfig;
 use dotenv::dotenv;
-use modules::{post::middleware::AuthMiddleware, post::handler::{AuthPostService, PostService}};
+use modules::{post::middleware::{AuthMiddleware, POST_READ_SCOPE, POST_WRITE_SCOPE}, post::handler::{AuthPostService, PostService}};
 use pgsql_libs::{create_db_pool, DbPool};
 use proto_libs::post_proto::{post_server::PostServer, protected_post_server::ProtectedPostServer};
 use redis_libs::{redis_connect, RedisPool};
@@ -39,7 +39,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let address = match config.apps.address.parse(){
         Ok(data)=>data,
         Err(error)=>{
-            service_logger::err_logger(&handler_name, "main", "main.config_get_address", &error);
+            service_logger::err_logger(handler_name, "main", "main.config_get_address", &error);
             panic!("{}",error)
         }
     };
@@ -61,19 +61,26 @@ async fn main() -> Result<(), Box<dyn Error>> {
             service_logger::info_logger(handler_name, "main", "main.get_redis_pool");
             redis_pool},
         Err(err)=>{
-            service_logger::err_logger(&handler_name, "main","main.get_redis_pool", &err);
+            service_logger::err_logger(handler_name, "main","main.get_redis_pool", &err);
             panic!("{}",err)
         }
 
     };
 
     let redis_arc = Arc::new(redis_connect);
-    let auth_middleware = AuthMiddleware::new(redis_arc.clone());
+    let auth_middleware = AuthMiddleware::new(redis_arc.clone(), config.jwt, config.jwks.url);
+    auth_middleware.spawn_key_refresh(Duration::from_secs(config.jwks.refresh_interval));
 
     let post = PostService::new(db_pool.clone());
     let protected_post = AuthPostService::new(db_pool.clone());
 
-    let interceptor = move |req: Request<()>| auth_middleware.auth_check(req);
+    let service_middleware = auth_middleware.clone();
+    #[allow(clippy::result_large_err)]
+    let read_interceptor = move |req: Request<()>| service_middleware.service_check(req, POST_READ_SCOPE);
+    #[allow(clippy::result_large_err)]
+    let write_interceptor = move |req: Request<()>| {
+        auth_middleware.service_check(req, POST_WRITE_SCOPE).and_then(|req| auth_middleware.auth_check(req))
+    };
 
     let services = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(proto_libs::POST_FILE_DESCRIPTOR_SET)
@@ -81,8 +88,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     Server::builder()
         .add_service(services)
-        .add_service(ProtectedPostServer::with_interceptor(protected_post, interceptor))
-        .add_service(PostServer::new(post))
+        .add_service(ProtectedPostServer::with_interceptor(protected_post, write_interceptor))
+        .add_service(PostServer::with_interceptor(post, read_interceptor))
         .serve(address)
         .await?;
 
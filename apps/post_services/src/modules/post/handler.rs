@@ -91,6 +91,7 @@ impl AuthPostService{
         Self { dbpool }
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn user_validate<T>(
         req: &Request<T>
     )->Result<AccessToken,Status>{
@@ -103,7 +104,7 @@ impl AuthPostService{
             },
             None=>{
                 Logger::warning_logger(handle_name, log_id, "validate_token","token not found");
-                return Err(Status::unauthenticated(format!("invalid token")))
+                Err(Status::unauthenticated("invalid token".to_string()))
             }
         }
     }
@@ -137,7 +138,7 @@ impl ProtectedPost for AuthPostService{
                 posts
             },
             Err(error)=>{
-                Logger::warning_logger(&handler_name, &log_id, "create_post.insert_db", &error);
+                Logger::warning_logger(handler_name, &log_id, "create_post.insert_db", &error);
                 return Err(Status::internal(error))
             }
         };
@@ -169,7 +170,7 @@ impl ProtectedPost for AuthPostService{
             content: data.content.clone(),
             title: data.title.clone(),
         };
-        match PostQuery::update_post(update_data, &self.dbpool).await{
+        match PostQuery::update_post(update_data, &user, &self.dbpool).await{
             Ok(posts)=>{
                 Logger::info_logger(handler_name, &log_id, "create_post.update_db");
                 let response:PostResponse = PostResponse{
@@ -183,7 +184,7 @@ impl ProtectedPost for AuthPostService{
                 Ok(Response::new(response))
             },
             Err(error)=>{
-                Logger::warning_logger(&handler_name, &log_id, "create_post.insert_db", &format!("{}",error));
+                Logger::warning_logger(handler_name, &log_id, "create_post.insert_db", &format!("{}",error));
                 Err(error)
             }
         }
@@ -201,7 +202,7 @@ impl ProtectedPost for AuthPostService{
         let data = request.get_ref();
         
 
-        match PostQuery::delete_post(user.id, data.post_id.parse::<Uuid>().unwrap(), &self.dbpool).await{
+        match PostQuery::delete_post(&user, data.post_id.parse::<Uuid>().unwrap(), &self.dbpool).await{
             Ok(delete_response)=>{
                 Logger::info_logger(handler_name, &log_id, "create_post.delete_db_data");
                 let response: DeleteResponse = DeleteResponse{
@@ -212,8 +213,8 @@ impl ProtectedPost for AuthPostService{
                 Ok(Response::new(response))
             },
             Err(error)=>{
-                Logger::warning_logger(&handler_name, &log_id, "create_post.insert_db", &format!("{}",error));
-                Err(Status::internal(error))
+                Logger::warning_logger(handler_name, &log_id, "create_post.insert_db", &error.to_string());
+                Err(error)
             }   
         }
     }
@@ -1,40 +1,301 @@
-use std::sync::Arc;
-use jwt_libs::decode_access_token;
-use r2d2_redis::redis::Commands;
+use std::{sync::{Arc, RwLock}, time::Duration};
+use jsonwebtoken::jwk::JwkSet;
+use jwt_libs::{decode_access_token, decode_service_token, error::JwtLibError, keys::JwtKeyring, types::{AccessToken, JwtClaimsConfig, ServiceToken}};
+use logger_libs::Logger;
+use redis_libs::{is_subject_disabled, is_token_revoked, RedisPool};
+use tokio::sync::Notify;
 use tonic::{Request, Status};
-use redis_libs::RedisPool;
+
+pub const SERVICE_AUTHORIZATION: &str = "x-service-authorization";
+pub const POST_READ_SCOPE: &str = "post:read";
+pub const POST_WRITE_SCOPE: &str = "post:write";
+
+#[allow(clippy::result_large_err)]
+fn bearer_token<T>(req: &Request<T>, key: &str) -> Result<String, Status> {
+    match req.metadata().get(key).and_then(|value| value.to_str().ok()) {
+        Some(value) => match value.strip_prefix("Bearer ") {
+            Some(token) if !token.trim().is_empty() => Ok(token.trim().to_string()),
+            _ => Err(Status::unauthenticated(format!("Invalid token: expected Bearer {}", key))),
+        },
+        None => Err(Status::unauthenticated(format!("Invalid token: missing {} metadata", key))),
+    }
+}
 
 #[derive(Clone)]
 pub struct AuthMiddleware {
     redis_pool: Arc<RedisPool>,
+    keyring: Arc<RwLock<JwtKeyring>>,
+    claims: JwtClaimsConfig,
+    jwks_url: String,
+    refresh_signal: Arc<Notify>,
 }
 
+// tonic interceptors have to fail with tonic::Status.
+#[allow(clippy::result_large_err)]
 impl AuthMiddleware {
-    pub fn new(redis_pool: Arc<RedisPool>) -> Self {
-        Self { redis_pool }
+    pub fn new(redis_pool: Arc<RedisPool>, claims: JwtClaimsConfig, jwks_url: String) -> Self {
+        Self {
+            redis_pool,
+            keyring: Arc::new(RwLock::new(JwtKeyring::new(claims.clone()))),
+            claims,
+            jwks_url,
+            refresh_signal: Arc::new(Notify::new()),
+        }
     }
 
-    pub fn auth_check(&self, mut req: Request<()>) -> Result<Request<()>, Status> {
-        let mut redis_conn = match self.redis_pool.get() {
-            Ok(conn) => conn,
-            Err(error) => return Err(Status::internal(format!("Redis error: {}", error))),
-        };
+    pub async fn refresh_keys(&self) -> Result<(), String> {
+        let jwks: JwkSet = reqwest::get(&self.jwks_url)
+            .await
+            .map_err(|error| format!("failed to fetch jwks: {}", error))?
+            .json()
+            .await
+            .map_err(|error| format!("invalid jwks response: {}", error))?;
+
+        let keyring = JwtKeyring::from_jwks(&jwks, &self.claims).map_err(|error| error.to_string())?;
+
+        match self.keyring.write() {
+            Ok(mut current) => {
+                *current = keyring;
+                Ok(())
+            },
+            Err(error) => Err(format!("jwks cache poisoned: {}", error)),
+        }
+    }
 
-        let redis_key = String::from("access_token");
+    pub fn spawn_key_refresh(&self, interval: Duration) {
+        let middleware = self.clone();
+        let handler_name = "auth_middleware.refresh_keys";
 
-        let token:String  = match redis_conn.get::<String,String>(redis_key) {
-            Ok(token) => token,
-            Err(_) => return Err(Status::unauthenticated("Invalid token: not found in Redis")),
+        tokio::spawn(async move {
+            loop {
+                match middleware.refresh_keys().await {
+                    Ok(_) => Logger::info_logger(handler_name, "jwks", "refresh_keys.fetch_jwks"),
+                    Err(error) => Logger::warning_logger(handler_name, "jwks", "refresh_keys.fetch_jwks", &error),
+                }
+
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {},
+                    _ = middleware.refresh_signal.notified() => {},
+                }
+            }
+        });
+    }
+
+    fn with_keyring<T>(&self, decode: impl FnOnce(&JwtKeyring) -> Result<T, JwtLibError>) -> Result<T, Status> {
+        let decoded = match self.keyring.read() {
+            Ok(keyring) => decode(&keyring),
+            Err(error) => return Err(Status::internal(format!("jwks cache poisoned: {}", error))),
         };
-        // **Perbaikan: Menghapus titik koma di bawah ini**
-        match decode_access_token(&token) {
-            Ok(decoded_token) => {
-                let access_token = decoded_token.claims.token;
 
-                req.extensions_mut().insert(Arc::new(access_token));
-                Ok(req) 
+        decoded.map_err(|error| {
+            if let JwtLibError::UnknownKey(_) = error {
+                self.refresh_signal.notify_one();
             }
-            Err(error) => Err(Status::unauthenticated(format!("Invalid token: {}", error))),
+            error.into()
+        })
+    }
+
+    fn check_revoked(&self, jti: &str, subject: &str, issued_at: i64) -> Result<(), Status> {
+        match is_token_revoked(&self.redis_pool, jti, subject, issued_at) {
+            Ok(false) => Ok(()),
+            Ok(true) => Err(JwtLibError::Revoked.into()),
+            Err(error) => Err(Status::internal(format!("Redis error: {}", error))),
+        }
+    }
+
+    pub fn verify_token(&self, token: &str) -> Result<AccessToken, Status> {
+        let claims = self.with_keyring(|keyring| decode_access_token(token, keyring))?.claims;
+        self.check_revoked(&claims.jti, &claims.sub, claims.iat)?;
+
+        match is_subject_disabled(&self.redis_pool, &claims.sub) {
+            Ok(false) => Ok(claims.token),
+            Ok(true) => Err(Status::permission_denied("account disabled")),
+            Err(error) => Err(Status::internal(format!("Redis error: {}", error))),
+        }
+    }
+
+    pub fn verify_service_token(&self, token: &str) -> Result<ServiceToken, Status> {
+        let claims = self.with_keyring(|keyring| decode_service_token(token, keyring))?.claims;
+        self.check_revoked(&claims.jti, &claims.sub, claims.iat)?;
+        Ok(claims.token)
+    }
+
+    pub fn auth_check(&self, mut req: Request<()>) -> Result<Request<()>, Status> {
+        let token = bearer_token(&req, "authorization")?;
+        let access_token = self.verify_token(&token)?;
+
+        req.extensions_mut().insert(Arc::new(access_token));
+        Ok(req)
+    }
+
+    // Every caller of post_services is a registered OAuth client; the scope decides which services it may reach.
+    pub fn service_check(&self, mut req: Request<()>, scope: &str) -> Result<Request<()>, Status> {
+        let token = bearer_token(&req, SERVICE_AUTHORIZATION)?;
+        let service_token = self.verify_service_token(&token)?;
+
+        if !service_token.has_scope(scope) {
+            Logger::warning_logger("auth_middleware.service_check", &service_token.client_id, "service_check.scope", scope);
+            return Err(Status::permission_denied(format!("client {} is missing scope: {}", service_token.client_id, scope)));
+        }
+
+        req.extensions_mut().insert(Arc::new(service_token));
+        Ok(req)
+    }
+}
+
+#[allow(clippy::result_large_err)]
+pub fn require_permission(user: &AccessToken, permission: &str) -> Result<(), Status> {
+    match user.has_permission(permission) {
+        true => Ok(()),
+        false => Err(Status::permission_denied(format!("missing permission: {}", permission))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jwt_libs::{
+        generate_access_token, generate_client_access_token, generate_refresh_token, generate_service_token,
+        testing::{access_token, keyring_with},
+        types::{ClientAccessToken, RefreshToken},
+    };
+    use redis_libs::{revoke_token, set_subject_disabled, testing::test_redis_pool};
+    use sqlx::types::Uuid;
+    use tonic::Code;
+
+    use super::*;
+
+    fn keyring(audience: &str, access_audience: Vec<String>) -> JwtKeyring {
+        keyring_with(JwtClaimsConfig { issuer: String::from("auth_services"), audience: String::from(audience), access_audience })
+    }
+
+    fn issuer_keyring() -> JwtKeyring {
+        keyring("auth_services", vec![String::from("auth_services"), String::from("post_services")])
+    }
+
+    fn middleware() -> AuthMiddleware {
+        let verifier_keyring = keyring("post_services", Vec::new());
+
+        let middleware = AuthMiddleware::new(Arc::new(test_redis_pool()), verifier_keyring.claims().clone(), String::new());
+        *middleware.keyring.write().unwrap() = verifier_keyring;
+
+        middleware
+    }
+
+    #[test]
+    fn accepts_access_token() {
+        let user = access_token().build();
+        let token = generate_access_token(user.clone(), &issuer_keyring()).unwrap();
+
+        assert_eq!(middleware().verify_token(&token).unwrap().id, user.id);
+    }
+
+    #[test]
+    fn rejects_refresh_token() {
+        let token = generate_refresh_token(RefreshToken { id: Uuid::new_v4() }, &issuer_keyring()).unwrap();
+
+        let status = middleware().verify_token(&token).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn rejects_client_access_token() {
+        // Even a client named after this service must not get its user tokens accepted here.
+        let client = ClientAccessToken { id: Uuid::new_v4(), client_id: String::from("post_services"), scopes: vec![String::from("openid")] };
+        let token = generate_client_access_token(client, &issuer_keyring()).unwrap();
+
+        let status = middleware().verify_token(&token).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    fn request(authorization: Option<&str>) -> Request<()> {
+        let mut req = Request::new(());
+        if let Some(value) = authorization {
+            req.metadata_mut().insert("authorization", value.parse().unwrap());
         }
+        req
+    }
+
+    #[test]
+    fn auth_check_attaches_access_token() {
+        let user = access_token().build();
+        let token = generate_access_token(user.clone(), &issuer_keyring()).unwrap();
+
+        let req = middleware().auth_check(request(Some(&format!("Bearer {}", token)))).unwrap();
+        assert_eq!(req.extensions().get::<Arc<AccessToken>>().unwrap().id, user.id);
+    }
+
+    #[test]
+    fn auth_check_rejects_missing_metadata() {
+        let status = middleware().auth_check(request(None)).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn auth_check_rejects_non_bearer_metadata() {
+        let token = generate_access_token(access_token().build(), &issuer_keyring()).unwrap();
+
+        let status = middleware().auth_check(request(Some(&format!("Basic {}", token)))).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn rejects_revoked_token() {
+        let token = generate_access_token(access_token().build(), &issuer_keyring()).unwrap();
+        let claims = decode_access_token(&token, &issuer_keyring()).unwrap().claims;
+
+        let middleware = middleware();
+        revoke_token(&middleware.redis_pool, &claims.jti, claims.remaining_lifetime()).unwrap();
+
+        let status = middleware.auth_check(request(Some(&format!("Bearer {}", token)))).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn rejects_disabled_account() {
+        let user = access_token().build();
+        let token = generate_access_token(user.clone(), &issuer_keyring()).unwrap();
+
+        let middleware = middleware();
+        set_subject_disabled(&middleware.redis_pool, &user.id.to_string(), true).unwrap();
+
+        let status = middleware.auth_check(request(Some(&format!("Bearer {}", token)))).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    fn service_request(scopes: &[&str]) -> Request<()> {
+        let service = ServiceToken { client_id: String::from("post_gateway"), scopes: scopes.iter().map(|scope| scope.to_string()).collect() };
+        let token = generate_service_token(service, &issuer_keyring()).unwrap();
+
+        let mut req = Request::new(());
+        req.metadata_mut().insert(SERVICE_AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        req
+    }
+
+    #[test]
+    fn service_check_requires_scope() {
+        let req = middleware().service_check(service_request(&[POST_READ_SCOPE]), POST_READ_SCOPE).unwrap();
+        assert_eq!(req.extensions().get::<Arc<ServiceToken>>().unwrap().client_id, "post_gateway");
+
+        let status = middleware().service_check(service_request(&[POST_READ_SCOPE]), POST_WRITE_SCOPE).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert_eq!(middleware().service_check(request(None), POST_READ_SCOPE).unwrap_err().code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn service_check_rejects_user_tokens() {
+        let token = generate_access_token(access_token().build(), &issuer_keyring()).unwrap();
+
+        let mut req = Request::new(());
+        req.metadata_mut().insert(SERVICE_AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+        assert_eq!(middleware().service_check(req, POST_READ_SCOPE).unwrap_err().code(), Code::Unauthenticated);
+    }
+
+    #[test]
+    fn require_permission_checks_token_permissions() {
+        let admin = access_token().username("admin").role("admin").permission("post:delete:any").build();
+        let user = access_token().build();
+
+        assert!(require_permission(&admin, "post:delete:any").is_ok());
+        assert_eq!(require_permission(&user, "post:delete:any").unwrap_err().code(), Code::PermissionDenied);
     }
 }
@@ -1,10 +1,14 @@
+use jwt_libs::types::AccessToken;
 use pgsql_libs::DbPool;
 use sqlx::{query, query_as, types::Uuid};
 use tonic::Status;
 
 use proto_libs::post_proto::PostResponse;
 
-use super::model::{CreatePost, PostPayload, QueryDeleteResponse, UpdatePost, UserPayload};
+use super::{middleware::require_permission, model::{CreatePost, PostPayload, QueryDeleteResponse, UpdatePost, UserPayload}};
+
+pub const UPDATE_ANY_POST: &str = "post:update:any";
+pub const DELETE_ANY_POST: &str = "post:delete:any";
 pub struct PostQuery;
 
 impl PostQuery{
@@ -97,6 +101,7 @@ impl PostQuery{
 
     pub async fn update_post(
         update_data: UpdatePost,
+        actor: &AccessToken,
         db_pool: &DbPool,
     ) -> Result<PostPayload, Status> {
         // Find the user by ID
@@ -108,12 +113,12 @@ impl PostQuery{
         // Find the post by ID
         let post = match Self::get_post_by_id(update_data.post_id, db_pool).await {
             Ok(Some(post)) => post,
-            Ok(None)=> return Err(Status::invalid_argument(format!("post not found: "))),
+            Ok(None)=> return Err(Status::invalid_argument("post not found: ".to_string())),
             Err(error) => return Err(Status::internal(format!("error database: {}",error))),
         };
         
         if user.id != post.user_id {
-            return Err(Status::unauthenticated("you are not the owner"))
+            require_permission(actor, UPDATE_ANY_POST)?;
         }
 
         // Update the post in the database
@@ -127,7 +132,7 @@ impl PostQuery{
             "#,
             update_data.title,
             update_data.content,
-            user.id,
+            post.user_id,
             post.id
         )
         .fetch_one(db_pool)
@@ -140,10 +145,10 @@ impl PostQuery{
     }
 
     pub async fn delete_post(
-        user_id: Uuid,
+        actor: &AccessToken,
         post_id: Uuid,
         db_pool: &DbPool
-    ) -> Result<QueryDeleteResponse, String> {
+    ) -> Result<QueryDeleteResponse, Status> {
 
         let post = query!(
             r#"
@@ -156,12 +161,12 @@ impl PostQuery{
     
         let post = match post {
             Ok(Some(post)) => post,
-            Ok(None) => return Err("Post not found".to_string()),
-            Err(error) => return Err(format!("Database error: {}", error)),
+            Ok(None) => return Err(Status::not_found("Post not found")),
+            Err(error) => return Err(Status::internal(format!("Database error: {}", error))),
         };
     
-        if post.user_id != user_id {
-            return Err("You do not have permission to delete this post".to_string());
+        if post.user_id != actor.id {
+            require_permission(actor, DELETE_ANY_POST)?;
         }
     
         let result = query!(
@@ -177,10 +182,10 @@ impl PostQuery{
             Ok(_) => Ok(
                 QueryDeleteResponse{
                     post_id,
-                    user_id
+                    user_id: post.user_id
                 }
             ),
-            Err(error) => Err(format!("Failed to delete post: {}", error)),
+            Err(error) => Err(Status::internal(format!("Failed to delete post: {}", error))),
         }
     }
     
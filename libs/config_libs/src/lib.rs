@@ -10,25 +10,13 @@ where
     dotenv().ok();
 
     println!("debug pat{}",config_path);
-    let settings= match Config::builder()
+    let settings= Config::builder()
                 .add_source(File::with_name(config_path))
                 .add_source(
                     Environment::with_prefix(prefix)
                         .separator("_"),
                 )
-                .build(){
-                    Ok(settings)=>settings,
-                    Err(error)=>return Err(error)
-                };
+                .build()?;
 
-    let deserialized = settings.try_deserialize();
-
-    match deserialized {
-        Ok(config)=>{
-            return Ok(config)
-        },
-        Err(error)=>{
-            return Err(error)
-        }
-    }
+    settings.try_deserialize()
 }
@@ -0,0 +1,92 @@
+use std::fmt;
+
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtLibError {
+    Expired,
+    NotYetValid,
+    InvalidSignature,
+    InvalidIssuer,
+    InvalidAudience,
+    Malformed(String),
+    WrongTokenType,
+    Revoked,
+    MissingKeyId,
+    UnknownKey(String),
+    KeyConfig(String),
+    Signing(String),
+}
+
+impl JwtLibError {
+    pub fn is_unauthorized(&self) -> bool {
+        !matches!(self, JwtLibError::KeyConfig(_) | JwtLibError::Signing(_))
+    }
+}
+
+impl fmt::Display for JwtLibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JwtLibError::Expired => write!(f, "token expired"),
+            JwtLibError::NotYetValid => write!(f, "token not yet valid"),
+            JwtLibError::InvalidSignature => write!(f, "invalid token signature"),
+            JwtLibError::InvalidIssuer => write!(f, "invalid token issuer"),
+            JwtLibError::InvalidAudience => write!(f, "invalid token audience"),
+            JwtLibError::Malformed(message) => write!(f, "malformed token: {}", message),
+            JwtLibError::WrongTokenType => write!(f, "wrong token type"),
+            JwtLibError::Revoked => write!(f, "token has been revoked"),
+            JwtLibError::MissingKeyId => write!(f, "token header has no kid"),
+            JwtLibError::UnknownKey(kid) => write!(f, "unknown jwt kid: {}", kid),
+            JwtLibError::KeyConfig(message) => write!(f, "jwt key error: {}", message),
+            JwtLibError::Signing(message) => write!(f, "failed to sign token: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for JwtLibError {}
+
+impl From<JwtError> for JwtLibError {
+    fn from(error: JwtError) -> Self {
+        match error.kind() {
+            ErrorKind::ExpiredSignature => JwtLibError::Expired,
+            ErrorKind::ImmatureSignature => JwtLibError::NotYetValid,
+            ErrorKind::InvalidSignature | ErrorKind::InvalidAlgorithm => JwtLibError::InvalidSignature,
+            ErrorKind::InvalidIssuer => JwtLibError::InvalidIssuer,
+            ErrorKind::InvalidAudience => JwtLibError::InvalidAudience,
+            ErrorKind::InvalidEcdsaKey
+            | ErrorKind::InvalidRsaKey(_)
+            | ErrorKind::RsaFailedSigning
+            | ErrorKind::InvalidKeyFormat => JwtLibError::KeyConfig(error.to_string()),
+            _ => JwtLibError::Malformed(error.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "actix")]
+impl actix_web::ResponseError for JwtLibError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self.is_unauthorized() {
+            true => actix_web::http::StatusCode::UNAUTHORIZED,
+            false => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> actix_web::HttpResponse {
+        match self.is_unauthorized() {
+            true => actix_web::HttpResponse::Unauthorized()
+                .json(serde_json::json!({"error": "Unauthorized", "message": self.to_string()})),
+            false => actix_web::HttpResponse::InternalServerError()
+                .json(serde_json::json!({"error": "InternalServerError", "message": self.to_string()})),
+        }
+    }
+}
+
+#[cfg(feature = "tonic")]
+impl From<JwtLibError> for tonic::Status {
+    fn from(error: JwtLibError) -> Self {
+        match error.is_unauthorized() {
+            true => tonic::Status::unauthenticated(error.to_string()),
+            false => tonic::Status::internal(error.to_string()),
+        }
+    }
+}
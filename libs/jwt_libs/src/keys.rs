@@ -0,0 +1,248 @@
+use std::fs;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use rsa::{
+    pkcs8::{der::pem, spki::SubjectPublicKeyInfoRef, DecodePublicKey},
+    traits::PublicKeyParts,
+    RsaPublicKey,
+};
+
+use crate::{error::JwtLibError, types::{JwtClaimsConfig, JwtConfig, JwtKeyConfig, KeyStatus, TokenLifetimes}};
+
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub status: KeyStatus,
+    encoding_key: Option<EncodingKey>,
+    decoding_key: DecodingKey,
+    public_jwk: Option<Jwk>,
+}
+
+#[derive(Clone, Default)]
+pub struct JwtKeyring {
+    keys: Vec<JwtKey>,
+    claims: JwtClaimsConfig,
+    lifetimes: TokenLifetimes,
+}
+
+fn read_pem(path: &Option<String>, name: &str) -> Result<Option<Vec<u8>>, JwtLibError> {
+    match path {
+        Some(path) => match fs::read(path) {
+            Ok(pem) => Ok(Some(pem)),
+            Err(error) => Err(JwtLibError::KeyConfig(format!("failed to read {} {}: {}", name, path, error))),
+        },
+        None => Ok(None),
+    }
+}
+
+fn public_jwk(kid: &str, algorithm: Algorithm, public_pem: &[u8]) -> Result<Jwk, JwtLibError> {
+    let pem_str = std::str::from_utf8(public_pem).map_err(|e| JwtLibError::KeyConfig(format!("invalid public key: {}", e)))?;
+
+    let (key_algorithm, algorithm_parameters) = match algorithm {
+        Algorithm::RS256 => {
+            let public_key = RsaPublicKey::from_public_key_pem(pem_str)
+                .map_err(|e| JwtLibError::KeyConfig(format!("invalid rsa public key: {}", e)))?;
+
+            (
+                KeyAlgorithm::RS256,
+                AlgorithmParameters::RSA(RSAKeyParameters {
+                    key_type: RSAKeyType::RSA,
+                    n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                    e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+                }),
+            )
+        }
+        Algorithm::EdDSA => {
+            let (_, der) = pem::decode_vec(public_pem).map_err(|e| JwtLibError::KeyConfig(format!("invalid ed25519 public key: {}", e)))?;
+            let spki = SubjectPublicKeyInfoRef::try_from(der.as_slice())
+                .map_err(|e| JwtLibError::KeyConfig(format!("invalid ed25519 public key: {}", e)))?;
+
+            (
+                KeyAlgorithm::EdDSA,
+                AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: URL_SAFE_NO_PAD.encode(spki.subject_public_key.raw_bytes()),
+                }),
+            )
+        }
+        _ => return Err(JwtLibError::KeyConfig(format!("{:?} keys cannot be published", algorithm))),
+    };
+
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: algorithm_parameters,
+    })
+}
+
+impl JwtKey {
+    pub fn from_config(config: &JwtKeyConfig) -> Result<Self, JwtLibError> {
+        let algorithm = match config.algorithm.parse::<Algorithm>() {
+            Ok(algorithm) => algorithm,
+            Err(error) => return Err(JwtLibError::KeyConfig(format!("invalid jwt algorithm {}: {}", config.algorithm, error))),
+        };
+
+        match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+                let secret = match &config.secret {
+                    Some(secret) if !secret.is_empty() => secret.as_bytes(),
+                    _ => return Err(JwtLibError::KeyConfig(format!("jwt secret is required for {:?}", algorithm))),
+                };
+
+                Ok(JwtKey {
+                    kid: config.kid.clone(),
+                    algorithm,
+                    status: config.status,
+                    encoding_key: Some(EncodingKey::from_secret(secret)),
+                    decoding_key: DecodingKey::from_secret(secret),
+                    public_jwk: None,
+                })
+            }
+            Algorithm::RS256 | Algorithm::EdDSA => {
+                let private_pem = read_pem(&config.private_key_path, "private key")?;
+                let public_pem = match read_pem(&config.public_key_path, "public key")? {
+                    Some(pem) => pem,
+                    None => return Err(JwtLibError::KeyConfig(format!("jwt public_key_path is required for {:?}", algorithm))),
+                };
+
+                let (encoding_key, decoding_key) = if algorithm == Algorithm::RS256 {
+                    (
+                        private_pem.map(|pem| EncodingKey::from_rsa_pem(&pem)).transpose(),
+                        DecodingKey::from_rsa_pem(&public_pem),
+                    )
+                } else {
+                    (
+                        private_pem.map(|pem| EncodingKey::from_ed_pem(&pem)).transpose(),
+                        DecodingKey::from_ed_pem(&public_pem),
+                    )
+                };
+
+                Ok(JwtKey {
+                    kid: config.kid.clone(),
+                    algorithm,
+                    status: config.status,
+                    encoding_key: encoding_key.map_err(|e| JwtLibError::KeyConfig(format!("invalid private key: {}", e)))?,
+                    decoding_key: decoding_key.map_err(|e| JwtLibError::KeyConfig(format!("invalid public key: {}", e)))?,
+                    public_jwk: Some(public_jwk(&config.kid, algorithm, &public_pem)?),
+                })
+            }
+            _ => Err(JwtLibError::KeyConfig(format!("unsupported jwt algorithm: {:?}", algorithm))),
+        }
+    }
+
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, JwtLibError> {
+        let kid = match &jwk.common.key_id {
+            Some(kid) => kid.clone(),
+            None => return Err(JwtLibError::KeyConfig(String::from("jwk without kid"))),
+        };
+
+        let algorithm = match jwk.common.key_algorithm {
+            Some(KeyAlgorithm::RS256) => Algorithm::RS256,
+            Some(KeyAlgorithm::EdDSA) => Algorithm::EdDSA,
+            other => return Err(JwtLibError::KeyConfig(format!("unsupported jwk algorithm for {}: {:?}", kid, other))),
+        };
+
+        let decoding_key = DecodingKey::from_jwk(jwk).map_err(|e| JwtLibError::KeyConfig(format!("invalid jwk {}: {}", kid, e)))?;
+
+        Ok(JwtKey {
+            kid,
+            algorithm,
+            status: KeyStatus::Active,
+            encoding_key: None,
+            decoding_key,
+            public_jwk: Some(jwk.clone()),
+        })
+    }
+
+    pub fn encoding_key(&self) -> Result<&EncodingKey, JwtLibError> {
+        match &self.encoding_key {
+            Some(key) => Ok(key),
+            None => Err(JwtLibError::KeyConfig(format!("no private key configured for {}, cannot sign tokens", self.kid))),
+        }
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+impl JwtKeyring {
+    pub fn new(claims: JwtClaimsConfig) -> Self {
+        JwtKeyring { keys: Vec::new(), claims, lifetimes: TokenLifetimes::default() }
+    }
+
+    pub fn from_config(config: &JwtConfig) -> Result<Self, JwtLibError> {
+        let mut keys: Vec<JwtKey> = Vec::new();
+
+        for key_config in &config.keys {
+            if keys.iter().any(|key| key.kid == key_config.kid) {
+                return Err(JwtLibError::KeyConfig(format!("duplicate jwt kid: {}", key_config.kid)));
+            }
+            keys.push(JwtKey::from_config(key_config)?);
+        }
+
+        let active = keys.iter().filter(|key| key.status == KeyStatus::Active).count();
+        if active > 1 {
+            return Err(JwtLibError::KeyConfig(format!("expected at most one active jwt key, found {}", active)));
+        }
+
+        Ok(JwtKeyring { keys, claims: config.claims.clone(), lifetimes: config.lifetimes })
+    }
+
+    pub fn from_jwks(jwks: &JwkSet, claims: &JwtClaimsConfig) -> Result<Self, JwtLibError> {
+        let keys = jwks.keys.iter().map(JwtKey::from_jwk).collect::<Result<Vec<JwtKey>, JwtLibError>>()?;
+
+        Ok(JwtKeyring { keys, claims: claims.clone(), lifetimes: TokenLifetimes::default() })
+    }
+
+    pub fn claims(&self) -> &JwtClaimsConfig {
+        &self.claims
+    }
+
+    pub fn lifetimes(&self) -> TokenLifetimes {
+        self.lifetimes
+    }
+
+    pub fn signing_key(&self) -> Result<&JwtKey, JwtLibError> {
+        match self.keys.iter().find(|key| key.status == KeyStatus::Active) {
+            Some(key) => Ok(key),
+            None => Err(JwtLibError::KeyConfig(String::from("no active jwt key configured"))),
+        }
+    }
+
+    pub fn verifying_key(&self, kid: Option<&str>) -> Result<&JwtKey, JwtLibError> {
+        let kid = match kid {
+            Some(kid) => kid,
+            None => return Err(JwtLibError::MissingKeyId),
+        };
+
+        match self.keys.iter().find(|key| key.kid == kid && key.status != KeyStatus::Retired) {
+            Some(key) => Ok(key),
+            None => Err(JwtLibError::UnknownKey(kid.to_string())),
+        }
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .keys
+                .iter()
+                .filter(|key| key.status != KeyStatus::Retired)
+                .filter_map(|key| key.public_jwk.clone())
+                .collect(),
+        }
+    }
+}
@@ -1,48 +1,287 @@
 use chrono::{Utc, Duration};
-use jsonwebtoken::{decode, encode, errors::Error as JwtError, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+pub mod error;
+pub mod keys;
 pub mod types;
-use types::{AccessToken, RefreshToken, TokenClaims};
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+use error::JwtLibError;
+use keys::JwtKeyring;
+use types::{AccessToken, ClientAccessToken, IdToken, IdTokenClaims, MfaPendingToken, RefreshToken, ServiceToken, TokenClaims, TokenSubject, TokenUse};
 
-impl<T: Serialize> TokenClaims<T> {
-    pub fn generate_token(data: T, duration: Duration) -> Result<String, String> {
-        let iat = Utc::now().timestamp();
-        let exp = (Utc::now() + duration).timestamp();
+fn sign_claims<C: Serialize>(claims: &C, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    let signing_key = keyring.signing_key()?;
 
-        let claims = TokenClaims { iat, exp, token: data };
-        let jwt_secret = EncodingKey::from_secret("secret_key".as_bytes());
-        encode(&Header::default(), &claims , &jwt_secret).map_err(|e| e.to_string())
+    let mut header = Header::new(signing_key.algorithm);
+    header.kid = Some(signing_key.kid.clone());
+
+    encode(&header, claims, signing_key.encoding_key()?).map_err(|e| JwtLibError::Signing(e.to_string()))
+}
+
+
+impl<T: Serialize + TokenSubject> TokenClaims<T> {
+    pub fn generate_token(data: T, token_use: TokenUse, audience: Vec<String>, duration: Duration, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+        let now = Utc::now();
+        let iat = now.timestamp();
+        let exp = (now + duration).timestamp();
+
+        let claims = TokenClaims {
+            iss: keyring.claims().issuer.clone(),
+            aud: audience,
+            sub: data.subject(),
+            jti: Uuid::new_v4().to_string(),
+            iat,
+            nbf: iat,
+            exp,
+            token_use,
+            token: data,
+        };
+        sign_claims(&claims, keyring)
     }
 }
 
-pub fn decode_token<T>(token: &str) -> Result<TokenData<TokenClaims<T>>, String>
+impl<T> TokenClaims<T> {
+    pub fn remaining_lifetime(&self) -> i64 {
+        self.exp - Utc::now().timestamp()
+    }
+}
+
+pub fn decode_token<T>(token: &str, token_use: TokenUse, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<T>>, JwtLibError>
 where
     T: for<'de> Deserialize <'de>,
 {
-    decode::<TokenClaims<T>>(
-        &token,
-        &DecodingKey::from_secret("secret_key".as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|e: JwtError| e.to_string())
+    let header = decode_header(token)?;
+    let verifying_key = keyring.verifying_key(header.kid.as_deref())?;
+
+    let mut validation = Validation::new(verifying_key.algorithm);
+    validation.set_issuer(&[&keyring.claims().issuer]);
+    validation.set_audience(&[&keyring.claims().audience]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+
+    let token_data = decode::<TokenClaims<T>>(
+        token,
+        verifying_key.decoding_key(),
+        &validation,
+    )?;
+
+    if token_data.claims.token_use != token_use {
+        return Err(JwtLibError::WrongTokenType);
+    }
+
+    Ok(token_data)
+}
+
+pub fn generate_refresh_token(data: RefreshToken, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    let audience = vec![keyring.claims().audience.clone()];
+    TokenClaims::<RefreshToken>::generate_token(data, TokenUse::Refresh, audience, Duration::seconds(keyring.lifetimes().refresh_token), keyring)
 }
 
-// Usage examples:
+fn access_audience(keyring: &JwtKeyring) -> Vec<String> {
+    match keyring.claims().access_audience.is_empty() {
+        true => vec![keyring.claims().audience.clone()],
+        false => keyring.claims().access_audience.clone(),
+    }
+}
+
+pub fn generate_access_token(data: AccessToken, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    TokenClaims::<AccessToken>::generate_token(data, TokenUse::Access, access_audience(keyring), Duration::seconds(keyring.lifetimes().access_token), keyring)
+}
 
-pub fn generate_refresh_token(data: RefreshToken) -> Result<String, String> {
-    TokenClaims::<RefreshToken>::generate_token(data, Duration::days(7))
+pub fn generate_service_token(data: ServiceToken, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    TokenClaims::<ServiceToken>::generate_token(data, TokenUse::Service, access_audience(keyring), Duration::seconds(keyring.lifetimes().service_token), keyring)
 }
 
-pub fn generate_access_token(data: AccessToken) -> Result<String, String> {
-    TokenClaims::<AccessToken>::generate_token(data, Duration::minutes(20))
+// Bound to the OAuth client through its audience, so AccessTokenMW and the resource services reject it.
+pub fn generate_client_access_token(data: ClientAccessToken, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    let audience = vec![data.client_id.clone()];
+    TokenClaims::<ClientAccessToken>::generate_token(data, TokenUse::Client, audience, Duration::seconds(keyring.lifetimes().access_token), keyring)
 }
 
-pub fn decode_refresh_token(token: &str) -> Result<TokenData<TokenClaims<RefreshToken>>, String> {
-    decode_token(token)
+// The audience of an ID token is the OAuth client it was issued to, not one of our services.
+pub fn generate_id_token(data: IdToken, client_id: &str, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    let now = Utc::now();
+    let claims = IdTokenClaims {
+        iss: keyring.claims().issuer.clone(),
+        aud: client_id.to_string(),
+        azp: client_id.to_string(),
+        iat: now.timestamp(),
+        exp: (now + Duration::seconds(keyring.lifetimes().id_token)).timestamp(),
+        token: data,
+    };
+    sign_claims(&claims, keyring)
 }
 
-pub fn decode_access_token(token: &str) -> Result<TokenData<TokenClaims<AccessToken>>, String> {
-    decode_token(token)
+// Only proves the password step; the audience is restricted to the issuer so resource services never accept it.
+pub fn generate_mfa_pending_token(data: MfaPendingToken, keyring: &JwtKeyring) -> Result<String, JwtLibError> {
+    let audience = vec![keyring.claims().audience.clone()];
+    TokenClaims::<MfaPendingToken>::generate_token(data, TokenUse::MfaPending, audience, Duration::seconds(keyring.lifetimes().mfa_pending), keyring)
+}
+
+pub fn decode_refresh_token(token: &str, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<RefreshToken>>, JwtLibError> {
+    decode_token(token, TokenUse::Refresh, keyring)
+}
+
+pub fn decode_access_token(token: &str, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<AccessToken>>, JwtLibError> {
+    decode_token(token, TokenUse::Access, keyring)
+}
+
+pub fn decode_service_token(token: &str, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<ServiceToken>>, JwtLibError> {
+    decode_token(token, TokenUse::Service, keyring)
+}
+
+// The audience is the client named inside the token, so it is checked after decoding instead of against the keyring.
+pub fn decode_client_access_token(token: &str, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<ClientAccessToken>>, JwtLibError> {
+    let header = decode_header(token)?;
+    let verifying_key = keyring.verifying_key(header.kid.as_deref())?;
+
+    let mut validation = Validation::new(verifying_key.algorithm);
+    validation.set_issuer(&[&keyring.claims().issuer]);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+    validation.validate_aud = false;
+
+    let token_data = decode::<TokenClaims<ClientAccessToken>>(token, verifying_key.decoding_key(), &validation)?;
+
+    if token_data.claims.token_use != TokenUse::Client {
+        return Err(JwtLibError::WrongTokenType);
+    }
+    if token_data.claims.aud != [token_data.claims.token.client_id.as_str()] {
+        return Err(JwtLibError::InvalidAudience);
+    }
+
+    Ok(token_data)
+}
+
+pub fn decode_id_token(token: &str, client_id: &str, keyring: &JwtKeyring) -> Result<TokenData<IdTokenClaims>, JwtLibError> {
+    let header = decode_header(token)?;
+    let verifying_key = keyring.verifying_key(header.kid.as_deref())?;
+
+    let mut validation = Validation::new(verifying_key.algorithm);
+    validation.set_issuer(&[&keyring.claims().issuer]);
+    validation.set_audience(&[client_id]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    Ok(decode::<IdTokenClaims>(token, verifying_key.decoding_key(), &validation)?)
+}
+
+pub fn decode_mfa_pending_token(token: &str, keyring: &JwtKeyring) -> Result<TokenData<TokenClaims<MfaPendingToken>>, JwtLibError> {
+    decode_token(token, TokenUse::MfaPending, keyring)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::JwtLibError;
+    use testing::keyring;
+    use uuid::Uuid;
+
+    fn admin_token() -> AccessToken {
+        testing::access_token().role("admin").permission("post:delete:any").build()
+    }
+
+    #[test]
+    fn access_token_round_trip() {
+        let keyring = keyring();
+        let token = generate_access_token(admin_token(), &keyring).unwrap();
+
+        let decoded = decode_access_token(&token, &keyring).unwrap();
+        assert_eq!(decoded.claims.token_use, TokenUse::Access);
+        assert!(decoded.claims.token.has_role("admin"));
+        assert!(decoded.claims.token.has_permission("post:delete:any"));
+        assert!(!decoded.claims.token.has_permission("post:update:any"));
+    }
+
+    #[test]
+    fn refresh_token_is_rejected_as_access_token() {
+        let keyring = keyring();
+        let token = generate_refresh_token(RefreshToken { id: Uuid::new_v4() }, &keyring).unwrap();
+
+        assert!(decode_access_token(&token, &keyring).is_err());
+    }
+
+    #[test]
+    fn token_use_is_checked_even_when_payload_matches() {
+        let keyring = keyring();
+        let audience = vec![keyring.claims().audience.clone()];
+        let token = TokenClaims::generate_token(admin_token(), TokenUse::Refresh, audience, Duration::minutes(5), &keyring).unwrap();
+
+        assert_eq!(decode_access_token(&token, &keyring).err(), Some(JwtLibError::WrongTokenType));
+    }
+
+    #[test]
+    fn access_token_is_rejected_as_refresh_token() {
+        let keyring = keyring();
+        let token = generate_access_token(admin_token(), &keyring).unwrap();
+
+        assert_eq!(decode_refresh_token(&token, &keyring).err(), Some(JwtLibError::WrongTokenType));
+    }
+
+    #[test]
+    fn mfa_pending_token_is_rejected_as_access_token() {
+        let keyring = keyring();
+        let token = generate_mfa_pending_token(MfaPendingToken { id: Uuid::new_v4() }, &keyring).unwrap();
+
+        assert_eq!(decode_mfa_pending_token(&token, &keyring).unwrap().claims.token_use, TokenUse::MfaPending);
+        assert!(decode_access_token(&token, &keyring).is_err());
+    }
+
+    #[test]
+    fn service_token_is_not_an_access_token() {
+        let keyring = keyring();
+        let token = generate_service_token(ServiceToken { client_id: String::from("post_gateway"), scopes: vec![String::from("post:read")] }, &keyring).unwrap();
+
+        let decoded = decode_service_token(&token, &keyring).unwrap();
+        assert_eq!(decoded.claims.sub, "post_gateway");
+        assert!(decoded.claims.token.has_scope("post:read"));
+        assert!(decode_access_token(&token, &keyring).is_err());
+    }
+
+    #[test]
+    fn client_access_token_is_bound_to_the_client() {
+        let keyring = keyring();
+        let data = ClientAccessToken { id: Uuid::new_v4(), client_id: String::from("internal-tool"), scopes: vec![String::from("openid")] };
+        let token = generate_client_access_token(data.clone(), &keyring).unwrap();
+
+        let decoded = decode_client_access_token(&token, &keyring).unwrap();
+        assert_eq!(decoded.claims.aud, vec![String::from("internal-tool")]);
+        assert_eq!(decoded.claims.token, data);
+        assert!(decoded.claims.token.has_scope("openid"));
+        assert!(decode_access_token(&token, &keyring).is_err());
+    }
+
+    #[test]
+    fn client_access_token_for_another_audience_is_rejected() {
+        let keyring = keyring();
+        let data = ClientAccessToken { id: Uuid::new_v4(), client_id: String::from("internal-tool"), scopes: Vec::new() };
+        let audience = vec![keyring.claims().audience.clone()];
+        let token = TokenClaims::generate_token(data, TokenUse::Client, audience, Duration::minutes(5), &keyring).unwrap();
+
+        assert_eq!(decode_client_access_token(&token, &keyring).err(), Some(JwtLibError::InvalidAudience));
+    }
+
+    #[test]
+    fn access_token_is_not_a_client_access_token() {
+        let keyring = keyring();
+        let token = generate_access_token(admin_token(), &keyring).unwrap();
+
+        assert!(decode_client_access_token(&token, &keyring).is_err());
+    }
+
+    #[test]
+    fn id_token_uses_flat_claims_for_the_client() {
+        let keyring = keyring();
+        let data = IdToken { sub: Uuid::new_v4().to_string(), nonce: Some(String::from("n-1")), email: Some(String::from("tester@mail.com")), ..Default::default() };
+        let token = generate_id_token(data.clone(), "internal-tool", &keyring).unwrap();
+
+        let decoded = decode_id_token(&token, "internal-tool", &keyring).unwrap();
+        assert_eq!(decoded.claims.token, data);
+        assert_eq!(decoded.claims.azp, "internal-tool");
+        assert!(decode_id_token(&token, "other-client", &keyring).is_err());
+        assert!(decode_access_token(&token, &keyring).is_err());
+    }
 }
@@ -0,0 +1,66 @@
+use uuid::Uuid;
+
+use crate::{keys::JwtKeyring, types::{AccessToken, JwtClaimsConfig, JwtConfig, JwtKeyConfig}};
+
+// HS256 keyring issued and verified as auth_services, the setup most tests need.
+pub fn keyring() -> JwtKeyring {
+    keyring_with(JwtClaimsConfig {
+        issuer: String::from("auth_services"),
+        audience: String::from("auth_services"),
+        access_audience: Vec::new(),
+    })
+}
+
+// Same shared secret with other claims, e.g. for a resource service that verifies tokens from the issuer's keyring.
+pub fn keyring_with(claims: JwtClaimsConfig) -> JwtKeyring {
+    JwtKeyring::from_config(&JwtConfig {
+        claims,
+        keys: vec![JwtKeyConfig {
+            kid: String::from("test"),
+            algorithm: String::from("HS256"),
+            secret: Some(String::from("test_secret")),
+            ..Default::default()
+        }],
+        ..Default::default()
+    })
+    .expect("valid test keyring")
+}
+
+pub struct AccessTokenBuilder {
+    token: AccessToken,
+}
+
+// A fresh user without roles or permissions; add them with the builder methods.
+pub fn access_token() -> AccessTokenBuilder {
+    AccessTokenBuilder {
+        token: AccessToken {
+            id: Uuid::new_v4(),
+            username: String::from("tester"),
+            email: String::from("tester@mail.com"),
+            roles: Vec::new(),
+            permissions: Vec::new(),
+        },
+    }
+}
+
+impl AccessTokenBuilder {
+    pub fn username(mut self, username: &str) -> Self {
+        self.token.username = username.to_string();
+        self.token.email = format!("{}@mail.com", username);
+        self
+    }
+
+    pub fn role(mut self, role: &str) -> Self {
+        self.token.roles.push(role.to_string());
+        self
+    }
+
+    pub fn permission(mut self, permission: &str) -> Self {
+        self.token.permissions.push(permission.to_string());
+        self
+    }
+
+    pub fn build(self) -> AccessToken {
+        self.token
+    }
+}
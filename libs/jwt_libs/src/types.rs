@@ -1,21 +1,261 @@
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenUse {
+    Access,
+    Refresh,
+    #[serde(rename = "mfa_pending")]
+    MfaPending,
+    Service,
+    Client,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct TokenClaims<T> {
+    pub iss: String,
+    pub aud: Vec<String>,
+    pub sub: String,
+    pub jti: String,
     pub iat: i64,
+    pub nbf: i64,
     pub exp: i64,
+    pub token_use: TokenUse,
     pub token: T,
 }
 
+pub trait TokenSubject {
+    fn subject(&self) -> String;
+}
+
 #[derive(Deserialize, Serialize,Clone,Debug)]
 pub struct AccessToken {
     pub id: Uuid,
     pub username: String,
     pub email: String,
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+impl AccessToken {
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|name| name == role)
+    }
+
+    pub fn has_permission(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|name| name == permission)
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct RefreshToken {
     pub id: Uuid,
 }
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MfaPendingToken {
+    pub id: Uuid,
+}
+
+// Issued to OAuth clients through client_credentials; there is no user behind it.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ServiceToken {
+    pub client_id: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl ServiceToken {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|name| name == scope)
+    }
+}
+
+// Issued to an OAuth client on behalf of a user; it only carries the granted scopes, never roles or permissions.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct ClientAccessToken {
+    pub id: Uuid,
+    pub client_id: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+impl ClientAccessToken {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|name| name == scope)
+    }
+}
+
+// OpenID Connect ID tokens use flat standard claims, so they do not go through TokenClaims.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct IdToken {
+    pub sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preferred_username: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub aud: String,
+    pub azp: String,
+    pub iat: i64,
+    pub exp: i64,
+    #[serde(flatten)]
+    pub token: IdToken,
+}
+
+impl TokenSubject for AccessToken {
+    fn subject(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl TokenSubject for RefreshToken {
+    fn subject(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl TokenSubject for MfaPendingToken {
+    fn subject(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl TokenSubject for ClientAccessToken {
+    fn subject(&self) -> String {
+        self.id.to_string()
+    }
+}
+
+impl TokenSubject for ServiceToken {
+    fn subject(&self) -> String {
+        self.client_id.clone()
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyStatus {
+    #[default]
+    Active,
+    Retiring,
+    Retired,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    pub algorithm: String,
+    #[serde(default)]
+    pub status: KeyStatus,
+    pub secret: Option<String>,
+    pub private_key_path: Option<String>,
+    pub public_key_path: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct JwtClaimsConfig {
+    pub issuer: String,
+    pub audience: String,
+    #[serde(default)]
+    pub access_audience: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct TokenLifetimes {
+    pub access_token: i64,
+    pub refresh_token: i64,
+    #[serde(default = "default_mfa_pending_lifetime")]
+    pub mfa_pending: i64,
+    #[serde(default = "default_id_token_lifetime")]
+    pub id_token: i64,
+    #[serde(default = "default_service_token_lifetime")]
+    pub service_token: i64,
+}
+
+fn default_mfa_pending_lifetime() -> i64 {
+    5 * 60
+}
+
+fn default_id_token_lifetime() -> i64 {
+    60 * 60
+}
+
+fn default_service_token_lifetime() -> i64 {
+    5 * 60
+}
+
+impl Default for TokenLifetimes {
+    fn default() -> Self {
+        TokenLifetimes {
+            access_token: 20 * 60,
+            refresh_token: 7 * 24 * 60 * 60,
+            mfa_pending: default_mfa_pending_lifetime(),
+            id_token: default_id_token_lifetime(),
+            service_token: default_service_token_lifetime(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq, Eq, Clone)]
+pub struct JwtConfig {
+    pub claims: JwtClaimsConfig,
+    #[serde(default)]
+    pub lifetimes: TokenLifetimes,
+    pub keys: Vec<JwtKeyConfig>,
+}
+
+impl JwtConfig {
+    // Relative key paths are resolved against the directory the keys are mounted in.
+    pub fn with_key_dir(mut self, key_dir: &str) -> Self {
+        let resolve = |path: &mut Option<String>| {
+            if let Some(file) = path.as_ref().filter(|file| Path::new(file).is_relative()) {
+                *path = Some(Path::new(key_dir).join(file).to_string_lossy().into_owned());
+            }
+        };
+
+        for key in &mut self.keys {
+            resolve(&mut key.private_key_path);
+            resolve(&mut key.public_key_path);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_paths_resolve_against_key_dir() {
+        let config = JwtConfig {
+            keys: vec![JwtKeyConfig {
+                kid: String::from("dev"),
+                algorithm: String::from("EdDSA"),
+                private_key_path: Some(String::from("dev_ed25519.pem")),
+                public_key_path: Some(String::from("/run/secrets/dev_ed25519.pub.pem")),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+        .with_key_dir("/etc/auth_services/keys");
+
+        assert_eq!(config.keys[0].private_key_path.as_deref(), Some("/etc/auth_services/keys/dev_ed25519.pem"));
+        assert_eq!(config.keys[0].public_key_path.as_deref(), Some("/run/secrets/dev_ed25519.pub.pem"));
+    }
+}
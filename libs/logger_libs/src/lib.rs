@@ -110,9 +110,7 @@ impl MaskData{
             })
             .collect();
     
-        let final_username = hashed_user.join(" "); 
-    
-        final_username
+        hashed_user.join(" ")
     }
     
     fn phone_mask(msisdn: &str) -> String {
@@ -12,7 +12,7 @@ pub fn rabbit_connect(rabbitmq_url: String, max_conn:usize) -> Result<RabbitMqPo
 
     let manager = Manager::new(config.url.unwrap(),ConnectionProperties::default());
 
-    match Pool::builder(manager)
+    Pool::builder(manager)
     .max_size(max_conn)
     .timeouts(Timeouts{
         wait:Some(Duration::from_secs(60)),
@@ -20,8 +20,5 @@ pub fn rabbit_connect(rabbitmq_url: String, max_conn:usize) -> Result<RabbitMqPo
         recycle:Some(Duration::from_secs(60))
     })
     .runtime(deadpool_lapin::Runtime::Tokio1)
-    .build() {
-        Ok(rabbit_pool)=>Ok(rabbit_pool),
-        Err(err)=>Err(err)
-    }
+    .build()
 }
\ No newline at end of file
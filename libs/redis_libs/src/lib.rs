@@ -1,29 +1,210 @@
-use r2d2_redis::{r2d2::{self, Pool, PooledConnection}, RedisConnectionManager};
+use r2d2_redis::{r2d2::{self, Pool, PooledConnection}, redis::Commands, RedisConnectionManager};
+
+pub mod rate_limit;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 pub type RedisPool = Pool<RedisConnectionManager>;
 
+const REVOKED_TOKEN_PREFIX: &str = "revoked_jti";
+const REVOKED_SUBJECT_PREFIX: &str = "revoked_sub";
+const REVOKE_EXEMPT_PREFIX: &str = "revoke_exempt_jti";
+const DISABLED_SUBJECT_PREFIX: &str = "disabled_sub";
+
 pub fn redis_connect(hostname:String,password:Option<String>,min_con:u32, max_conn:u32) -> Result<Pool<RedisConnectionManager>,r2d2::Error>{
-    let redis_password = match password {
-        Some(pwd) => pwd,
-        None => String::new(),
-    };
+    let redis_password = password.unwrap_or_default();
 
     let conn_url = format!("redis://{}@{}",redis_password,hostname);
 
     let manager: RedisConnectionManager = RedisConnectionManager::new(conn_url).expect("Invalid connection URL");
 
-    match Pool::builder()
+    Pool::builder()
         .min_idle(Some(min_con))
         .max_size(max_conn) 
-        .build(manager){
-            Ok(redis_pool)=>Ok(redis_pool),
-            Err(err) =>Err(err)
-        }
+        .build(manager)
 }
 
 pub fn create_redis_connection(redis_pool: &RedisPool) -> Result<PooledConnection<RedisConnectionManager>, r2d2::Error>{
-    match redis_pool.get(){
-        Ok(conn) => Ok(conn),
-        Err(error) => Err(error),
+    redis_pool.get()
+}
+
+fn revoked_token_key(jti: &str) -> String {
+    format!("{}:{}", REVOKED_TOKEN_PREFIX, jti)
+}
+
+pub fn revoke_token(redis_pool: &RedisPool, jti: &str, ttl: i64) -> Result<(), String> {
+    if ttl <= 0 {
+        return Ok(());
+    }
+
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    conn.set_ex::<String, i64, ()>(revoked_token_key(jti), ttl, ttl as usize)
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+fn revoked_subject_key(subject: &str) -> String {
+    format!("{}:{}", REVOKED_SUBJECT_PREFIX, subject)
+}
+
+pub fn revoke_subject_tokens(redis_pool: &RedisPool, subject: &str, issued_before: i64, ttl: i64) -> Result<(), String> {
+    if ttl <= 0 {
+        return Ok(());
     }
-}
\ No newline at end of file
+
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    conn.set_ex::<String, i64, ()>(revoked_subject_key(subject), issued_before, ttl as usize)
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+fn revoke_exempt_key(jti: &str) -> String {
+    format!("{}:{}", REVOKE_EXEMPT_PREFIX, jti)
+}
+
+// Keeps a token issued in the same second as a subject revocation alive, since iat cannot tell it apart from older tokens.
+pub fn exempt_token(redis_pool: &RedisPool, jti: &str, ttl: i64) -> Result<(), String> {
+    if ttl <= 0 {
+        return Ok(());
+    }
+
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    conn.set_ex::<String, i64, ()>(revoke_exempt_key(jti), ttl, ttl as usize)
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+pub fn is_token_revoked(redis_pool: &RedisPool, jti: &str, subject: &str, issued_at: i64) -> Result<bool, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    let revoked = conn.exists::<String, bool>(revoked_token_key(jti))
+        .map_err(|error| format!("error redis: {}", error))?;
+    if revoked {
+        return Ok(true);
+    }
+
+    let issued_before = conn.get::<String, Option<i64>>(revoked_subject_key(subject))
+        .map_err(|error| format!("error redis: {}", error))?;
+    if issued_before.is_none_or(|issued_before| issued_at > issued_before) {
+        return Ok(false);
+    }
+
+    let exempt = conn.exists::<String, bool>(revoke_exempt_key(jti))
+        .map_err(|error| format!("error redis: {}", error))?;
+    Ok(!exempt)
+}
+
+fn disabled_subject_key(subject: &str) -> String {
+    format!("{}:{}", DISABLED_SUBJECT_PREFIX, subject)
+}
+
+// Disabled flags have no TTL; they stay until the account is enabled again.
+pub fn set_subject_disabled(redis_pool: &RedisPool, subject: &str, disabled: bool) -> Result<(), String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    match disabled {
+        true => conn.set::<String, i64, ()>(disabled_subject_key(subject), 1),
+        false => conn.del::<String, ()>(disabled_subject_key(subject)),
+    }
+    .map_err(|error| format!("error redis: {}", error))
+}
+
+pub fn is_subject_disabled(redis_pool: &RedisPool, subject: &str) -> Result<bool, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+    conn.exists::<String, bool>(disabled_subject_key(subject))
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+pub fn store_one_time(redis_pool: &RedisPool, key: &str, value: &str, ttl: i64) -> Result<(), String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+    conn.set_ex::<&str, &str, ()>(key, value, ttl as usize)
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+pub fn fetch_value(redis_pool: &RedisPool, key: &str) -> Result<Option<String>, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+    conn.get::<&str, Option<String>>(key)
+        .map_err(|error| format!("error redis: {}", error))
+}
+
+// Only the caller whose DEL removes the key gets the value, so concurrent takes cannot both succeed.
+pub fn take_one_time(redis_pool: &RedisPool, key: &str) -> Result<Option<String>, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    let value = conn.get::<&str, Option<String>>(key)
+        .map_err(|error| format!("error redis: {}", error))?;
+    let removed = conn.del::<&str, i64>(key)
+        .map_err(|error| format!("error redis: {}", error))?;
+
+    Ok(value.filter(|_| removed > 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revoked_token_is_reported() {
+        let redis_pool = testing::test_redis_pool();
+
+        assert!(!is_token_revoked(&redis_pool, "jti-1", "user-1", 100).unwrap());
+        revoke_token(&redis_pool, "jti-1", 60).unwrap();
+        assert!(is_token_revoked(&redis_pool, "jti-1", "user-1", 100).unwrap());
+        assert!(!is_token_revoked(&redis_pool, "jti-2", "user-1", 100).unwrap());
+    }
+
+    #[test]
+    fn expired_token_is_not_stored() {
+        let redis_pool = testing::test_redis_pool();
+
+        revoke_token(&redis_pool, "jti-1", 0).unwrap();
+        assert!(!is_token_revoked(&redis_pool, "jti-1", "user-1", 100).unwrap());
+    }
+
+    #[test]
+    fn subject_revocation_covers_older_tokens() {
+        let redis_pool = testing::test_redis_pool();
+
+        revoke_subject_tokens(&redis_pool, "user-1", 100, 60).unwrap();
+        assert!(is_token_revoked(&redis_pool, "jti-1", "user-1", 99).unwrap());
+        assert!(is_token_revoked(&redis_pool, "jti-1", "user-1", 100).unwrap());
+        assert!(!is_token_revoked(&redis_pool, "jti-1", "user-1", 101).unwrap());
+        assert!(!is_token_revoked(&redis_pool, "jti-1", "user-2", 99).unwrap());
+    }
+
+    #[test]
+    fn exempt_token_survives_subject_revocation() {
+        let redis_pool = testing::test_redis_pool();
+
+        revoke_subject_tokens(&redis_pool, "user-1", 100, 60).unwrap();
+        exempt_token(&redis_pool, "jti-new", 60).unwrap();
+        assert!(!is_token_revoked(&redis_pool, "jti-new", "user-1", 100).unwrap());
+        assert!(is_token_revoked(&redis_pool, "jti-old", "user-1", 100).unwrap());
+
+        revoke_token(&redis_pool, "jti-new", 60).unwrap();
+        assert!(is_token_revoked(&redis_pool, "jti-new", "user-1", 100).unwrap());
+    }
+
+    #[test]
+    fn disabled_subject_is_reported_until_enabled() {
+        let redis_pool = testing::test_redis_pool();
+
+        set_subject_disabled(&redis_pool, "user-1", true).unwrap();
+        assert!(is_subject_disabled(&redis_pool, "user-1").unwrap());
+        assert!(!is_subject_disabled(&redis_pool, "user-2").unwrap());
+
+        set_subject_disabled(&redis_pool, "user-1", false).unwrap();
+        assert!(!is_subject_disabled(&redis_pool, "user-1").unwrap());
+    }
+
+    #[test]
+    fn one_time_value_is_taken_once() {
+        let redis_pool = testing::test_redis_pool();
+
+        store_one_time(&redis_pool, "oauth_state:1", "payload", 60).unwrap();
+        assert_eq!(fetch_value(&redis_pool, "oauth_state:1").unwrap(), Some(String::from("payload")));
+        assert_eq!(take_one_time(&redis_pool, "oauth_state:1").unwrap(), Some(String::from("payload")));
+        assert_eq!(take_one_time(&redis_pool, "oauth_state:1").unwrap(), None);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use r2d2_redis::redis::{pipe, Commands};
+
+use crate::{create_redis_connection, RedisPool};
+
+static HIT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: i64 },
+}
+
+fn now_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_millis() as i64).unwrap_or_default()
+}
+
+// Sliding-window log: each hit is a sorted-set member scored by its timestamp, and hits older than the window are trimmed.
+// Trim, add and count run in one MULTI so concurrent hits always count each other; a limited hit takes its member back out.
+pub fn sliding_window_hit(redis_pool: &RedisPool, key: &str, limit: u32, window: i64) -> Result<RateLimitDecision, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+    let now = now_millis();
+    let window_millis = window * 1000;
+    let member = format!("{}-{}", now, HIT_SEQUENCE.fetch_add(1, Ordering::Relaxed));
+
+    let (hits,): (u32,) = pipe()
+        .atomic()
+        .zrembyscore(key, "-inf", now - window_millis).ignore()
+        .zadd(key, &member, now).ignore()
+        .expire(key, window as usize).ignore()
+        .zcard(key)
+        .query(&mut *conn)
+        .map_err(|error| format!("error redis: {}", error))?;
+    if hits <= limit {
+        return Ok(RateLimitDecision::Allowed);
+    }
+
+    conn.zrem::<&str, &str, ()>(key, &member)
+        .map_err(|error| format!("error redis: {}", error))?;
+
+    let oldest: Vec<(String, f64)> = conn.zrange_withscores(key, 0, 0)
+        .map_err(|error| format!("error redis: {}", error))?;
+    let retry_after_millis = oldest
+        .first()
+        .map(|(_, score)| *score as i64 + window_millis - now)
+        .unwrap_or(window_millis);
+
+    Ok(RateLimitDecision::Limited { retry_after: (retry_after_millis + 999) / 1000 })
+}
+
+pub fn increment_counter(redis_pool: &RedisPool, key: &str, ttl: i64) -> Result<i64, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    let count: i64 = conn.incr(key, 1).map_err(|error| format!("error redis: {}", error))?;
+    conn.expire::<&str, ()>(key, ttl as usize).map_err(|error| format!("error redis: {}", error))?;
+
+    Ok(count)
+}
+
+pub fn set_lock(redis_pool: &RedisPool, key: &str, ttl: i64) -> Result<(), String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    conn.set_ex::<&str, i64, ()>(key, 1, ttl as usize).map_err(|error| format!("error redis: {}", error))
+}
+
+pub fn lock_remaining(redis_pool: &RedisPool, key: &str) -> Result<Option<i64>, String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    let ttl: i64 = conn.ttl(key).map_err(|error| format!("error redis: {}", error))?;
+    Ok(if ttl > 0 { Some(ttl) } else { None })
+}
+
+pub fn delete_key(redis_pool: &RedisPool, key: &str) -> Result<(), String> {
+    let mut conn = create_redis_connection(redis_pool).map_err(|error| format!("error redis connection: {}", error))?;
+
+    conn.del::<&str, ()>(key).map_err(|error| format!("error redis: {}", error))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::test_redis_pool;
+
+    use super::*;
+
+    #[test]
+    fn sliding_window_limits_after_threshold() {
+        let redis_pool = test_redis_pool();
+
+        for _ in 0..3 {
+            assert_eq!(sliding_window_hit(&redis_pool, "login:ip", 3, 60).unwrap(), RateLimitDecision::Allowed);
+        }
+
+        match sliding_window_hit(&redis_pool, "login:ip", 3, 60).unwrap() {
+            RateLimitDecision::Limited { retry_after } => assert!(retry_after > 0 && retry_after <= 60),
+            RateLimitDecision::Allowed => panic!("fourth hit should be limited"),
+        }
+        assert_eq!(sliding_window_hit(&redis_pool, "login:other", 3, 60).unwrap(), RateLimitDecision::Allowed);
+    }
+
+    #[test]
+    fn concurrent_hits_never_exceed_the_limit() {
+        let redis_pool = test_redis_pool();
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let redis_pool = redis_pool.clone();
+                std::thread::spawn(move || sliding_window_hit(&redis_pool, "login:burst", 5, 60).unwrap())
+            })
+            .collect();
+        let allowed = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|decision| *decision == RateLimitDecision::Allowed)
+            .count();
+        assert_eq!(allowed, 5);
+    }
+
+    #[test]
+    fn lock_reports_remaining_time() {
+        let redis_pool = test_redis_pool();
+
+        assert_eq!(lock_remaining(&redis_pool, "lock").unwrap(), None);
+        set_lock(&redis_pool, "lock", 30).unwrap();
+        assert!(lock_remaining(&redis_pool, "lock").unwrap().is_some_and(|ttl| ttl <= 30));
+        delete_key(&redis_pool, "lock").unwrap();
+        assert_eq!(lock_remaining(&redis_pool, "lock").unwrap(), None);
+    }
+
+    #[test]
+    fn counter_increments() {
+        let redis_pool = test_redis_pool();
+
+        assert_eq!(increment_counter(&redis_pool, "failures", 60).unwrap(), 1);
+        assert_eq!(increment_counter(&redis_pool, "failures", 60).unwrap(), 2);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use r2d2_redis::{r2d2::Pool, RedisConnectionManager};
+
+use crate::RedisPool;
+
+enum Value {
+    String(Vec<u8>),
+    SortedSet(Vec<(f64, Vec<u8>)>),
+}
+
+type Store = Arc<Mutex<HashMap<String, (Value, Option<Instant>)>>>;
+
+// In-process stand-in for the handful of commands redis_libs issues, so tests don't need a Redis server.
+pub fn test_redis_pool() -> RedisPool {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind test redis");
+    let address = listener.local_addr().expect("test redis address");
+    let store: Store = Arc::new(Mutex::new(HashMap::new()));
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let store = store.clone();
+            thread::spawn(move || serve(stream, store));
+        }
+    });
+
+    let manager = RedisConnectionManager::new(format!("redis://{}", address)).expect("test redis url");
+    Pool::builder().max_size(2).build_unchecked(manager)
+}
+
+fn read_line(reader: &mut BufReader<TcpStream>) -> Option<String> {
+    let mut line = String::new();
+    match reader.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end().to_string()),
+    }
+}
+
+fn read_command(reader: &mut BufReader<TcpStream>) -> Option<Vec<Vec<u8>>> {
+    let count: usize = read_line(reader)?.strip_prefix('*')?.parse().ok()?;
+    let mut args = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let len: usize = read_line(reader)?.strip_prefix('$')?.parse().ok()?;
+        let mut arg = vec![0; len + 2];
+        reader.read_exact(&mut arg).ok()?;
+        arg.truncate(len);
+        args.push(arg);
+    }
+
+    Some(args)
+}
+
+fn text(arg: &[u8]) -> String {
+    String::from_utf8_lossy(arg).to_string()
+}
+
+fn number<T: std::str::FromStr + Default>(arg: &[u8]) -> T {
+    text(arg).parse().unwrap_or_default()
+}
+
+fn bulk(value: &[u8]) -> Vec<u8> {
+    [format!("${}\r\n", value.len()).into_bytes(), value.to_vec(), b"\r\n".to_vec()].concat()
+}
+
+fn integer(value: i64) -> Vec<u8> {
+    format!(":{}\r\n", value).into_bytes()
+}
+
+fn score_bound(arg: &[u8]) -> f64 {
+    match text(arg).as_str() {
+        "-inf" => f64::NEG_INFINITY,
+        "+inf" | "inf" => f64::INFINITY,
+        value => value.parse().unwrap_or_default(),
+    }
+}
+
+// MULTI queues the following commands and EXEC runs them under one store lock, like a Redis transaction.
+fn serve(stream: TcpStream, store: Store) {
+    let mut writer = stream.try_clone().expect("clone test redis stream");
+    let mut reader = BufReader::new(stream);
+    let mut transaction: Option<Vec<Vec<Vec<u8>>>> = None;
+
+    while let Some(args) = read_command(&mut reader) {
+        let command = text(&args[0]).to_uppercase();
+
+        let reply: Vec<u8> = if command == "MULTI" {
+            transaction = Some(Vec::new());
+            b"+OK\r\n".to_vec()
+        } else if command == "EXEC" {
+            let commands = transaction.take().unwrap_or_default();
+            let mut store = store.lock().unwrap();
+            let mut reply = format!("*{}\r\n", commands.len()).into_bytes();
+            for queued in commands {
+                reply.extend(execute(&mut store, &queued));
+            }
+            reply
+        } else if let Some(commands) = transaction.as_mut() {
+            commands.push(args);
+            b"+QUEUED\r\n".to_vec()
+        } else {
+            execute(&mut store.lock().unwrap(), &args)
+        };
+
+        if writer.write_all(&reply).is_err() {
+            break;
+        }
+    }
+}
+
+fn execute(store: &mut HashMap<String, (Value, Option<Instant>)>, args: &[Vec<u8>]) -> Vec<u8> {
+    let command = text(&args[0]).to_uppercase();
+    let key = args.get(1).map(|key| text(key)).unwrap_or_default();
+    store.retain(|_, (_, expires_at)| expires_at.is_none_or(|at| at > Instant::now()));
+
+    match command.as_str() {
+        "PING" => b"+PONG\r\n".to_vec(),
+        "SET" => {
+            store.insert(key, (Value::String(args[2].clone()), None));
+            b"+OK\r\n".to_vec()
+        },
+        "SETEX" => {
+            let expires_at = Instant::now() + Duration::from_secs(number(&args[2]));
+            store.insert(key, (Value::String(args[3].clone()), Some(expires_at)));
+            b"+OK\r\n".to_vec()
+        },
+        "GET" => match store.get(&key) {
+            Some((Value::String(value), _)) => bulk(value),
+            _ => b"$-1\r\n".to_vec(),
+        },
+        "INCRBY" => {
+            let entry = store.entry(key).or_insert((Value::String(b"0".to_vec()), None));
+            let value = match &entry.0 {
+                Value::String(value) => number::<i64>(value) + number::<i64>(&args[2]),
+                Value::SortedSet(_) => number(&args[2]),
+            };
+            entry.0 = Value::String(value.to_string().into_bytes());
+            integer(value)
+        },
+        "EXISTS" => integer(store.contains_key(&key) as i64),
+        "DEL" => integer(store.remove(&key).is_some() as i64),
+        "EXPIRE" => match store.get_mut(&key) {
+            Some(entry) => {
+                entry.1 = Some(Instant::now() + Duration::from_secs(number(&args[2])));
+                integer(1)
+            },
+            None => integer(0),
+        },
+        "TTL" => match store.get(&key) {
+            Some((_, Some(expires_at))) => integer(expires_at.saturating_duration_since(Instant::now()).as_secs_f64().ceil() as i64),
+            Some((_, None)) => integer(-1),
+            None => integer(-2),
+        },
+        "ZADD" => {
+            let entry = store.entry(key).or_insert((Value::SortedSet(Vec::new()), None));
+            let mut added = 0;
+            if let Value::SortedSet(members) = &mut entry.0 {
+                for pair in args[2..].chunks(2) {
+                    members.retain(|(_, member)| *member != pair[1]);
+                    members.push((score_bound(&pair[0]), pair[1].clone()));
+                    added += 1;
+                }
+                members.sort_by(|a, b| a.0.total_cmp(&b.0));
+            }
+            integer(added)
+        },
+        "ZREM" => match store.get_mut(&key) {
+            Some((Value::SortedSet(members), _)) => {
+                let before = members.len();
+                members.retain(|(_, member)| !args[2..].contains(member));
+                integer((before - members.len()) as i64)
+            },
+            _ => integer(0),
+        },
+        "ZREMRANGEBYSCORE" => match store.get_mut(&key) {
+            Some((Value::SortedSet(members), _)) => {
+                let (min, max) = (score_bound(&args[2]), score_bound(&args[3]));
+                let before = members.len();
+                members.retain(|(score, _)| *score < min || *score > max);
+                integer((before - members.len()) as i64)
+            },
+            _ => integer(0),
+        },
+        "ZCARD" => match store.get(&key) {
+            Some((Value::SortedSet(members), _)) => integer(members.len() as i64),
+            _ => integer(0),
+        },
+        "ZRANGE" => match store.get(&key) {
+            Some((Value::SortedSet(members), _)) => {
+                let len = members.len() as i64;
+                let resolve = |index: i64| if index < 0 { len + index } else { index };
+                let (start, stop) = (resolve(number(&args[2])).max(0), resolve(number(&args[3])).min(len - 1));
+                let with_scores = args.get(4).is_some_and(|arg| text(arg).eq_ignore_ascii_case("WITHSCORES"));
+
+                let selected: Vec<&(f64, Vec<u8>)> = if start > stop {
+                    Vec::new()
+                } else {
+                    members[start as usize..=stop as usize].iter().collect()
+                };
+                let mut reply = format!("*{}\r\n", selected.len() * if with_scores { 2 } else { 1 }).into_bytes();
+                for (score, member) in selected {
+                    reply.extend(bulk(member));
+                    if with_scores {
+                        reply.extend(bulk(score.to_string().as_bytes()));
+                    }
+                }
+                reply
+            },
+            _ => b"*0\r\n".to_vec(),
+        },
+        _ => format!("-ERR unknown command '{}'\r\n", command).into_bytes(),
+    }
+}
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_refresh_token_family_id;
+
+ALTER TABLE "refresh_token"
+    DROP COLUMN IF EXISTS used_at,
+    DROP COLUMN IF EXISTS family_id;
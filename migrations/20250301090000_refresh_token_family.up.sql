@@ -0,0 +1,6 @@
+-- Add up migration script here
+ALTER TABLE "refresh_token"
+    ADD COLUMN IF NOT EXISTS family_id UUID NOT NULL DEFAULT gen_random_uuid(),
+    ADD COLUMN IF NOT EXISTS used_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_refresh_token_family_id ON "refresh_token" (family_id);
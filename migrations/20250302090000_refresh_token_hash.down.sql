@@ -0,0 +1,14 @@
+-- Add down migration script here
+-- hashed tokens cannot be restored, so existing sessions are dropped
+DELETE FROM "refresh_token";
+
+DROP INDEX IF EXISTS idx_refresh_token_expires_at;
+DROP INDEX IF EXISTS idx_refresh_token_token_hash;
+
+ALTER TABLE "refresh_token"
+    DROP COLUMN IF EXISTS ip,
+    DROP COLUMN IF EXISTS user_agent,
+    DROP COLUMN IF EXISTS expires_at,
+    DROP COLUMN IF EXISTS created_at;
+
+ALTER TABLE "refresh_token" RENAME COLUMN token_hash TO refreshtoken;
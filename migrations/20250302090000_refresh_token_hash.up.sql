@@ -0,0 +1,15 @@
+-- Add up migration script here
+ALTER TABLE "refresh_token" RENAME COLUMN refreshtoken TO token_hash;
+
+UPDATE "refresh_token" SET token_hash = encode(sha256(convert_to(token_hash, 'UTF8')), 'hex');
+
+ALTER TABLE "refresh_token"
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ NOT NULL DEFAULT now() + INTERVAL '7 days',
+    ADD COLUMN IF NOT EXISTS user_agent TEXT,
+    ADD COLUMN IF NOT EXISTS ip TEXT;
+
+ALTER TABLE "refresh_token" ALTER COLUMN expires_at DROP DEFAULT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_refresh_token_token_hash ON "refresh_token" (token_hash);
+CREATE INDEX IF NOT EXISTS idx_refresh_token_expires_at ON "refresh_token" (expires_at);
@@ -0,0 +1,7 @@
+-- Add down migration script here
+ALTER TABLE "user"
+    DROP COLUMN IF EXISTS totp_recovery_codes,
+    DROP COLUMN IF EXISTS totp_last_counter,
+    DROP COLUMN IF EXISTS totp_pending_secret,
+    DROP COLUMN IF EXISTS totp_secret,
+    DROP COLUMN IF EXISTS totp_enabled;
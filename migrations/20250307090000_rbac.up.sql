@@ -0,0 +1,43 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS "role"(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS "permission"(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name TEXT NOT NULL UNIQUE,
+    description TEXT NOT NULL DEFAULT ''
+);
+
+CREATE TABLE IF NOT EXISTS "role_permission"(
+    role_id UUID NOT NULL,
+    permission_id UUID NOT NULL,
+    PRIMARY KEY (role_id, permission_id),
+    CONSTRAINT fk_role FOREIGN KEY (role_id) REFERENCES "role" (id) ON DELETE CASCADE,
+    CONSTRAINT fk_permission FOREIGN KEY (permission_id) REFERENCES "permission" (id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS "user_role"(
+    user_id UUID NOT NULL,
+    role_id UUID NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, role_id),
+    CONSTRAINT fk_user FOREIGN KEY (user_id) REFERENCES "user" (id) ON DELETE CASCADE,
+    CONSTRAINT fk_role FOREIGN KEY (role_id) REFERENCES "role" (id) ON DELETE CASCADE
+);
+
+INSERT INTO "role" (name, description) VALUES
+    ('admin', 'full access to every resource')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "permission" (name, description) VALUES
+    ('post:update:any', 'update posts owned by any user'),
+    ('post:delete:any', 'delete posts owned by any user')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO "role_permission" (role_id, permission_id)
+SELECT r.id, p.id FROM "role" r, "permission" p
+WHERE r.name = 'admin'
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,6 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "admin_audit";
+
+ALTER TABLE "user"
+    DROP COLUMN IF EXISTS disabled_at,
+    DROP COLUMN IF EXISTS created_at;
@@ -0,0 +1,17 @@
+-- Add up migration script here
+ALTER TABLE "user"
+    ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    ADD COLUMN IF NOT EXISTS disabled_at TIMESTAMPTZ;
+
+CREATE TABLE IF NOT EXISTS "admin_audit"(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    actor_id UUID,
+    target_user_id UUID,
+    action TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_actor FOREIGN KEY (actor_id) REFERENCES "user" (id) ON DELETE SET NULL,
+    CONSTRAINT fk_target_user FOREIGN KEY (target_user_id) REFERENCES "user" (id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_audit_target_user ON "admin_audit" (target_user_id, created_at);
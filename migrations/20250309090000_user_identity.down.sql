@@ -0,0 +1,7 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS "user_identity";
+
+-- Fails while accounts without a password or phone number exist; remove or complete them first.
+ALTER TABLE "user"
+    ALTER COLUMN phonenumber SET NOT NULL,
+    ALTER COLUMN password SET NOT NULL;
@@ -0,0 +1,19 @@
+-- Add up migration script here
+-- Accounts created through a social login have neither a password nor a phone number until the user sets them.
+ALTER TABLE "user"
+    ALTER COLUMN password DROP NOT NULL,
+    ALTER COLUMN phonenumber DROP NOT NULL;
+
+CREATE TABLE IF NOT EXISTS "user_identity"(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL,
+    provider TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    email TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_login_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT fk_user FOREIGN KEY (user_id) REFERENCES "user" (id) ON DELETE CASCADE,
+    CONSTRAINT uq_user_identity_subject UNIQUE (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identity_user_id ON "user_identity" (user_id);
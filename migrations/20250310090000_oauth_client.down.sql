@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS idx_refresh_token_client_id;
+
+ALTER TABLE "refresh_token"
+    DROP CONSTRAINT IF EXISTS fk_oauth_client,
+    DROP COLUMN IF EXISTS scope,
+    DROP COLUMN IF EXISTS client_id;
+
+DROP TABLE IF EXISTS "oauth_client";
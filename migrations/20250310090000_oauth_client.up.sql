@@ -0,0 +1,21 @@
+-- Add up migration script here
+-- Clients of our OpenID Connect provider. Public clients have no secret and must always use PKCE.
+CREATE TABLE IF NOT EXISTS "oauth_client"(
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    client_id TEXT NOT NULL,
+    client_secret_hash TEXT,
+    name TEXT NOT NULL,
+    redirect_uris TEXT[] NOT NULL DEFAULT '{}',
+    grant_types TEXT[] NOT NULL DEFAULT '{}',
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT uq_oauth_client_client_id UNIQUE (client_id)
+);
+
+-- Refresh tokens handed to an OAuth client stay bound to it and to the scope it was granted.
+ALTER TABLE "refresh_token"
+    ADD COLUMN IF NOT EXISTS client_id TEXT,
+    ADD COLUMN IF NOT EXISTS scope TEXT,
+    ADD CONSTRAINT fk_oauth_client FOREIGN KEY (client_id) REFERENCES "oauth_client" (client_id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_refresh_token_client_id ON "refresh_token" (client_id);
@@ -0,0 +1,11 @@
+-- Add up migration script here
+-- Scopes a user has approved for an OAuth client; /authorize only asks again for scopes outside this list.
+CREATE TABLE IF NOT EXISTS "oauth_consent"(
+    user_id UUID NOT NULL,
+    client_id TEXT NOT NULL,
+    scopes TEXT[] NOT NULL DEFAULT '{}',
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (user_id, client_id),
+    CONSTRAINT fk_user FOREIGN KEY (user_id) REFERENCES "user" (id) ON DELETE CASCADE,
+    CONSTRAINT fk_oauth_client FOREIGN KEY (client_id) REFERENCES "oauth_client" (client_id) ON DELETE CASCADE
+);
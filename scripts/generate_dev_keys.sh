@@ -0,0 +1,18 @@
+#!/usr/bin/env sh
+# Generates the Ed25519 signing key pair used by auth_services in development.
+# Point JWT_KEY_DIR at the output directory; production keys belong in a secret mount.
+set -eu
+
+KEY_DIR="${1:-apps/auth_services/keys}"
+mkdir -p "$KEY_DIR"
+
+if [ -f "$KEY_DIR/dev_ed25519.pem" ]; then
+    echo "$KEY_DIR/dev_ed25519.pem already exists, leaving it in place"
+    exit 0
+fi
+
+openssl genpkey -algorithm ed25519 -out "$KEY_DIR/dev_ed25519.pem"
+openssl pkey -in "$KEY_DIR/dev_ed25519.pem" -pubout -out "$KEY_DIR/dev_ed25519.pub.pem"
+chmod 600 "$KEY_DIR/dev_ed25519.pem"
+
+echo "JWT_KEY_DIR=$KEY_DIR"